@@ -0,0 +1,74 @@
+;;; idgrab-mode.el --- Major mode for idGrab scripts
+
+;; idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+;;
+;; Copyright (C) 2024 David Gow <david@davidgow.net>
+;;
+;; This software is provided 'as-is', without any express or implied warranty.
+;; In no event will the authors be held liable for any damages arising from
+;; the use of this software.
+;;
+;; Permission is granted to anyone to use this software for any purpose, including
+;; commercial applications, and to alter it and redistribute it freely, subject
+;; to the following restrictions.
+;;   1. The origin of this software must not be misrepresented; you must not
+;;      claim that you wrote the original software. If you use this software in
+;;      a product, an acknowledgment in the product documentation would be
+;;      appreciated but is not required.
+;;   2. Altered source versions must be plainly marked as such, and must not be
+;;      misrepresented as being the original software.
+;;   3. This notice may not be removed or altered from any source distribution.
+
+;;; Commentary:
+
+;; Highlights the keywords, strings and comments in idGrab scripts.
+;; Put this file somewhere in your load-path, and add:
+;;
+;;   (require 'idgrab-mode)
+;;
+;; to your init file. Files ending in .idgrab or .gfx use this mode
+;; automatically, as does any script starting with the mode line:
+;;
+;;   // -*- mode: idgrab -*-
+
+;;; Code:
+
+(defvar idgrab-mode-keywords
+  '("Extension" "Game"
+    "Fonts" "FontsMasked" "Bitmaps" "BitmapsMasked" "Sprites"
+    "Tiles8" "Tiles8Masked" "Tiles16" "Tiles16Masked" "Tiles32" "Tiles32Masked"
+    "HeaderChunks" "Externs" "Chunk" "Article" "B8000Text" "Terminator" "Demo"
+    "Lump")
+  "Keywords in idGrab scripts.")
+
+(defvar idgrab-mode-font-lock-keywords
+  `((,(regexp-opt idgrab-mode-keywords 'words) . font-lock-keyword-face)
+    ("\\_<-?\\(?:0[xX][0-9a-fA-F]+\\|0[bB][01]+\\|[0-9]+\\)\\_>"
+     . font-lock-constant-face))
+  "Highlighting for idGrab scripts.")
+
+(defvar idgrab-mode-syntax-table
+  (let ((table (make-syntax-table)))
+    ;; Comments run from '#' or '//' to the end of the line.
+    (modify-syntax-entry ?# "<" table)
+    (modify-syntax-entry ?/ ". 12" table)
+    (modify-syntax-entry ?\n ">" table)
+    (modify-syntax-entry ?\" "\"" table)
+    (modify-syntax-entry ?_ "_" table)
+    table)
+  "Syntax table for idGrab scripts.")
+
+;;;###autoload
+(define-derived-mode idgrab-mode prog-mode "idGrab"
+  "Major mode for editing idGrab scripts."
+  :syntax-table idgrab-mode-syntax-table
+  (setq-local comment-start "# ")
+  (setq-local comment-start-skip "\\(?:#\\|//\\)+\\s-*")
+  (setq-local font-lock-defaults '(idgrab-mode-font-lock-keywords)))
+
+;;;###autoload
+(add-to-list 'auto-mode-alist '("\\.\\(?:idgrab\\|gfx\\)\\'" . idgrab-mode))
+
+(provide 'idgrab-mode)
+
+;;; idgrab-mode.el ends here
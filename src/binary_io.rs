@@ -18,42 +18,56 @@
  *      misrepresented as being the original software.
  *   3. This notice may not be removed or altered from any source distribution.
  */
+
+/*
+ * Helpers for reading and writing binary files, such as GFXINFOE.
+ *
+ * Every multi-byte function names its byte order explicitly:
+ *   le: little-endian, as used by the DOS ID-engine games. read_le16() and
+ *       read_le32() read a DOS "word" and "dword" (or "long") respectively.
+ *   be: big-endian, as used for some embedded metadata (e.g. in ports to
+ *       big-endian machines).
+ *
+ * None of these panic: any I/O error (including running out of data, or an
+ * array count too large to fit in memory) is returned as an io::Error. Not
+ * everything here is used by idGrab itself.
+ */
 #![allow(dead_code)]
 
 pub fn read_byte(reader: &mut dyn std::io::Read) -> std::io::Result<u8> {
 	let mut out_byte: u8 = 0;
 	reader.read_exact(std::slice::from_mut(&mut out_byte))?;
-	return Ok(out_byte);
+	Ok(out_byte)
 }
 
 pub fn read_le16(reader: &mut dyn std::io::Read) -> std::io::Result<u16> {
-	let mut raw_bytes = [0 as u8; 2];
+	let mut raw_bytes = [0u8; 2];
 	reader.read_exact(&mut raw_bytes)?;
-	return Ok((raw_bytes[1] as u16) << 8 | (raw_bytes[0] as u16));
+	Ok((raw_bytes[1] as u16) << 8 | (raw_bytes[0] as u16))
 }
 
 pub fn read_le32(reader: &mut dyn std::io::Read) -> std::io::Result<u32> {
-	let mut raw_bytes = [0 as u8; 4];
+	let mut raw_bytes = [0u8; 4];
 	reader.read_exact(&mut raw_bytes)?;
-	return Ok((raw_bytes[3] as u32) << 24
+	Ok((raw_bytes[3] as u32) << 24
 		| (raw_bytes[2] as u32) << 16
 		| (raw_bytes[1] as u32) << 8
-		| (raw_bytes[0] as u32));
+		| (raw_bytes[0] as u32))
 }
 
 pub fn read_be16(reader: &mut dyn std::io::Read) -> std::io::Result<u16> {
-	let mut raw_bytes = [0 as u8; 2];
+	let mut raw_bytes = [0u8; 2];
 	reader.read_exact(&mut raw_bytes)?;
-	return Ok((raw_bytes[0] as u16) << 8 | (raw_bytes[1] as u16));
+	Ok((raw_bytes[0] as u16) << 8 | (raw_bytes[1] as u16))
 }
 
 pub fn read_be32(reader: &mut dyn std::io::Read) -> std::io::Result<u32> {
-	let mut raw_bytes = [0 as u8; 4];
+	let mut raw_bytes = [0u8; 4];
 	reader.read_exact(&mut raw_bytes)?;
-	return Ok((raw_bytes[0] as u32) << 24
+	Ok((raw_bytes[0] as u32) << 24
 		| (raw_bytes[1] as u32) << 16
 		| (raw_bytes[2] as u32) << 8
-		| (raw_bytes[3] as u32));
+		| (raw_bytes[3] as u32))
 }
 
 pub fn write_byte(out_byte: u8, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
@@ -89,3 +103,253 @@ pub fn write_le32(out_val: u32, writer: &mut dyn std::io::Write) -> std::io::Res
 	];
 	writer.write_all(&raw_bytes)
 }
+
+/*
+ * Reads count elements of elem_size bytes each. The buffer grows as data
+ * arrives, so a bogus count runs out of data rather than memory.
+ */
+fn read_array_bytes(
+	count: usize,
+	elem_size: usize,
+	reader: &mut dyn std::io::Read,
+) -> std::io::Result<Vec<u8>> {
+	use std::io::Read;
+
+	let len = count.checked_mul(elem_size).ok_or_else(|| {
+		std::io::Error::new(
+			std::io::ErrorKind::InvalidInput,
+			format!("{} elements of {} bytes is too large", count, elem_size),
+		)
+	})?;
+	let mut raw_bytes = Vec::new();
+	reader.take(len as u64).read_to_end(&mut raw_bytes)?;
+	if raw_bytes.len() < len {
+		return Err(std::io::ErrorKind::UnexpectedEof.into());
+	}
+	Ok(raw_bytes)
+}
+
+pub fn read_be16_array(count: usize, reader: &mut dyn std::io::Read) -> std::io::Result<Vec<u16>> {
+	let raw_bytes = read_array_bytes(count, 2, reader)?;
+	Ok(raw_bytes
+		.chunks(2)
+		.map(|b| (b[0] as u16) << 8 | (b[1] as u16))
+		.collect())
+}
+
+pub fn read_be32_array(count: usize, reader: &mut dyn std::io::Read) -> std::io::Result<Vec<u32>> {
+	let raw_bytes = read_array_bytes(count, 4, reader)?;
+	Ok(raw_bytes
+		.chunks(4)
+		.map(|b| {
+			(b[0] as u32) << 24
+				| (b[1] as u32) << 16 | (b[2] as u32) << 8
+				| (b[3] as u32)
+		})
+		.collect())
+}
+
+pub fn write_be16_array(data: &[u16], writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+	let raw_bytes: Vec<u8> = data
+		.iter()
+		.flat_map(|&val| vec![(val >> 8) as u8, (val & 0xFF) as u8])
+		.collect();
+	writer.write_all(&raw_bytes)
+}
+
+pub fn write_be32_array(data: &[u32], writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+	let raw_bytes: Vec<u8> = data
+		.iter()
+		.flat_map(|&val| {
+			vec![
+				(val >> 24) as u8,
+				(val >> 16) as u8,
+				(val >> 8) as u8,
+				(val & 0xFF) as u8,
+			]
+		})
+		.collect();
+	writer.write_all(&raw_bytes)
+}
+
+/*
+ * Reads a null-terminated string from a field of at most max_len bytes.
+ * Reading stops after the null (which is consumed), or after max_len bytes if
+ * there's no null. Invalid UTF-8 is replaced rather than treated as an error.
+ */
+pub fn read_c_string(max_len: usize, reader: &mut dyn std::io::Read) -> std::io::Result<String> {
+	let mut raw_bytes = Vec::new();
+	while raw_bytes.len() < max_len {
+		let byte = read_byte(reader)?;
+		if byte == 0 {
+			break;
+		}
+		raw_bytes.push(byte);
+	}
+	Ok(String::from_utf8_lossy(&raw_bytes).into_owned())
+}
+
+/*
+ * Writes a string into a max_len byte field: truncated to max_len - 1 bytes,
+ * null-terminated, and padded with zeroes to fill the field.
+ */
+pub fn write_c_string(
+	out_str: &str,
+	max_len: usize,
+	writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+	let mut raw_bytes = vec![0u8; max_len];
+	let str_len = out_str.len().min(max_len.saturating_sub(1));
+	raw_bytes[..str_len].copy_from_slice(&out_str.as_bytes()[..str_len]);
+	writer.write_all(&raw_bytes)
+}
+
+// The size of the (stack) buffer used by copy_bytes().
+const COPY_BUFFER_SIZE: usize = 4096;
+
+/*
+ * Copies exactly count bytes from reader to writer, a buffer at a time, so the
+ * whole section is never in memory. If the reader runs out first, the error is
+ * UnexpectedEof, and some (but not necessarily all) of the data read will have
+ * been written.
+ */
+pub fn copy_bytes(
+	count: usize,
+	reader: &mut dyn std::io::Read,
+	writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+	let mut buffer = [0u8; COPY_BUFFER_SIZE];
+	let mut remaining = count;
+	while remaining > 0 {
+		let piece = &mut buffer[..remaining.min(COPY_BUFFER_SIZE)];
+		reader.read_exact(piece)?;
+		writer.write_all(piece)?;
+		remaining -= piece.len();
+	}
+	Ok(())
+}
+
+/*
+ * As copy_bytes(), but everything read is written, even if the reader runs
+ * out, and the error then says how many bytes were actually copied.
+ */
+pub fn copy_bytes_checked(
+	count: usize,
+	reader: &mut dyn std::io::Read,
+	writer: &mut dyn std::io::Write,
+) -> std::io::Result<()> {
+	let mut buffer = [0u8; COPY_BUFFER_SIZE];
+	let mut copied = 0;
+	while copied < count {
+		let piece_len = (count - copied).min(COPY_BUFFER_SIZE);
+		let read_len = match reader.read(&mut buffer[..piece_len]) {
+			Ok(0) => {
+				return Err(std::io::Error::new(
+					std::io::ErrorKind::UnexpectedEof,
+					format!(
+						"Expected to copy {} bytes, but only copied {}",
+						count, copied
+					),
+				));
+			}
+			Ok(read_len) => read_len,
+			Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+			Err(e) => return Err(e),
+		};
+		writer.write_all(&buffer[..read_len])?;
+		copied += read_len;
+	}
+	Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	#[test]
+	fn oversized_arrays() {
+		let mut reader = &[0u8, 1, 2, 3][..];
+		let err = read_be16_array(usize::MAX, &mut reader).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+		let err = read_be32_array(usize::MAX / 2, &mut reader).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::InvalidInput);
+		/* Large, but not overflowing, counts just run out of data. */
+		let err = read_be16_array(usize::MAX / 4, &mut reader).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+	}
+
+	#[test]
+	fn c_string_round_trip() {
+		let mut out = Vec::new();
+		write_c_string("CK4", 8, &mut out).unwrap();
+		assert_eq!(out, b"CK4\0\0\0\0\0");
+		let mut reader = &out[..];
+		assert_eq!(read_c_string(8, &mut reader).unwrap(), "CK4");
+		/* The reader is left just after the null. */
+		assert_eq!(reader.len(), 4);
+	}
+
+	#[test]
+	fn c_string_truncation() {
+		let mut out = Vec::new();
+		write_c_string("GRAPHICS", 8, &mut out).unwrap();
+		assert_eq!(out, b"GRAPHIC\0");
+
+		/* Exactly max_len - 1 characters fit, with the null. */
+		let mut out = Vec::new();
+		write_c_string("EGAHEAD", 8, &mut out).unwrap();
+		assert_eq!(out, b"EGAHEAD\0");
+
+		/* A field with no null gives all max_len characters. */
+		let mut reader = &b"EGAGRAPHCK4"[..];
+		assert_eq!(read_c_string(8, &mut reader).unwrap(), "EGAGRAPH");
+		assert_eq!(reader, b"CK4");
+	}
+
+	#[test]
+	fn copy_bytes_exact() {
+		/* Bigger than the buffer, so it takes more than one piece. */
+		let data: Vec<u8> = (0..10000).map(|i| i as u8).collect();
+		let mut reader = &data[..];
+		let mut out = Vec::new();
+		copy_bytes(9000, &mut reader, &mut out).unwrap();
+		assert_eq!(out, &data[..9000]);
+		assert_eq!(reader.len(), 1000);
+
+		let mut out = Vec::new();
+		copy_bytes_checked(1000, &mut reader, &mut out).unwrap();
+		assert_eq!(out, &data[9000..]);
+	}
+
+	#[test]
+	fn copy_bytes_short() {
+		let data = [1u8, 2, 3];
+		let err = copy_bytes(4, &mut &data[..], &mut Vec::new()).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+
+		let mut out = Vec::new();
+		let err = copy_bytes_checked(4, &mut &data[..], &mut out).unwrap_err();
+		assert_eq!(err.kind(), std::io::ErrorKind::UnexpectedEof);
+		assert_eq!(
+			err.to_string(),
+			"Expected to copy 4 bytes, but only copied 3"
+		);
+		assert_eq!(out, data);
+	}
+
+	#[test]
+	fn be_arrays() {
+		let mut out = Vec::new();
+		write_be16_array(&[0x1234u16, 0x5678u16], &mut out).unwrap();
+		assert_eq!(out, [0x12, 0x34, 0x56, 0x78]);
+		assert_eq!(read_be16_array(2, &mut &out[..]).unwrap(), [0x1234, 0x5678]);
+
+		let mut out = Vec::new();
+		write_be32_array(&[0x12345678u32, 0x9ABCDEF0u32], &mut out).unwrap();
+		assert_eq!(out, [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE, 0xF0]);
+		assert_eq!(
+			read_be32_array(2, &mut &out[..]).unwrap(),
+			[0x12345678, 0x9ABCDEF0]
+		);
+	}
+}
@@ -0,0 +1,674 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+/* The argument (if any) taken by a command-line option. */
+pub enum OptionArg {
+	None,
+	Filename,
+	Choice(&'static [&'static str]),
+	/* Free-form text, shown in the usage as <name>. */
+	Text(&'static str),
+	/* Two free-form words, shown in the usage as <first> <second>. */
+	TextPair(&'static str, &'static str),
+}
+
+pub struct CliOption {
+	pub name: &'static str,
+	pub arg: OptionArg,
+	pub help: &'static str,
+}
+
+/*
+ * Every option idgrab accepts after the script name. This drives both the
+ * usage text and the shell completion scripts, so keep it in sync with
+ * the argument handling in main().
+ */
+pub const OPTIONS: &[CliOption] = &[
+	CliOption {
+		name: "--no-create-dirs",
+		arg: OptionArg::None,
+		help: "Don't create missing directories for output files.",
+	},
+	CliOption {
+		name: "--allow-semicolon-comments",
+		arg: OptionArg::None,
+		help: "Treat ';' as the start of a comment in scripts, as well as '#'.",
+	},
+	CliOption {
+		name: "--check-lumps",
+		arg: OptionArg::None,
+		help: "Warn about named chunks which aren't in any lump.",
+	},
+	CliOption {
+		name: "--strict-lumps",
+		arg: OptionArg::None,
+		help: "Fail if any named chunks aren't in a lump, or if a lump crosses\nfrom one section into another.",
+	},
+	CliOption {
+		name: "--gfxinfo",
+		arg: OptionArg::Filename,
+		help: "Generates a GFXINFO(E) file for use with TED or Omnispeak",
+	},
+	CliOption {
+		name: "--script",
+		arg: OptionArg::Filename,
+		help: "Writes the script back out, with its sections in chunk order.",
+	},
+	CliOption {
+		name: "--all",
+		arg: OptionArg::Filename,
+		help: "Writes PREFIX.gfx, PREFIX.def, PREFIX_gfx.txt, GFXEXT.H and GFXEXT.EQU,\nusing the options given before it. EXT is the script's Extension, or TMP.",
+	},
+	CliOption {
+		name: "--json",
+		arg: OptionArg::Filename,
+		help: "Writes everything the script defines as JSON.",
+	},
+	CliOption {
+		name: "--modid",
+		arg: OptionArg::Filename,
+		help: "Writes a modid/ugrab compatible .def file.",
+	},
+	CliOption {
+		name: "--modid-explicit-zero",
+		arg: OptionArg::None,
+		help: "Write TILE8 and TILE8M sections in modid .def files even with no tiles.",
+	},
+	CliOption {
+		name: "--modid-comments",
+		arg: OptionArg::None,
+		help: "Comment where each section and lump starts in modid .def files.",
+	},
+	CliOption {
+		name: "--modid-skip-empty",
+		arg: OptionArg::None,
+		help: "Leave sections with no chunks out of modid .def files.",
+	},
+	CliOption {
+		name: "--omnispeak",
+		arg: OptionArg::Filename,
+		help: "Generates an omnispeak-compatible GFXCHUNKS variable file",
+	},
+	CliOption {
+		name: "--omnispeak-arrays",
+		arg: OptionArg::None,
+		help: "List each omnispeak section as name/chunk arrays, not per-chunk %ints.",
+	},
+	CliOption {
+		name: "--omnispeak-no-font-vars",
+		arg: OptionArg::None,
+		help: "Don't set NUMFONT(M) and STARTFONT(M) in omnispeak files.",
+	},
+	CliOption {
+		name: "--omnispeak-no-font-section-vars",
+		arg: OptionArg::None,
+		help: "Don't set NUMFONTS(M), FONTS_START and FONTSM_START in omnispeak files.",
+	},
+	CliOption {
+		name: "--omnispeak-no-comment",
+		arg: OptionArg::None,
+		help: "Don't start omnispeak files with a comment saying idGrab generated them.",
+	},
+	CliOption {
+		name: "--omnispeak-no-tile-vars",
+		arg: OptionArg::None,
+		help: "Don't set NUMTILE* and STARTTILE* in omnispeak files.",
+	},
+	CliOption {
+		name: "--omnispeak-no-section-vars",
+		arg: OptionArg::None,
+		help: "Don't set any NUM* and START* variables in omnispeak files.",
+	},
+	CliOption {
+		name: "--omnispeak-struct-slot-prefix",
+		arg: OptionArg::Text("prefix"),
+		help: "Prefix for header chunk variables in omnispeak files, if there aren't 3 (default: STRUCTSLOT_).",
+	},
+	CliOption {
+		name: "--omnispeak-demo-aliases",
+		arg: OptionArg::None,
+		help: "Also set STARTDEMO and NUMDEMOS in omnispeak files.",
+	},
+	CliOption {
+		name: "--omnispeak-chunk-prefix",
+		arg: OptionArg::Text("prefix"),
+		help: "Prefix for Chunk variables in omnispeak files (default: EXTERN_).",
+	},
+	CliOption {
+		name: "--omnispeak-b8000text-prefix",
+		arg: OptionArg::Text("prefix"),
+		help: "Prefix for B8000Text variables in omnispeak files (default: EXTERN_).",
+	},
+	CliOption {
+		name: "--omnispeak-article-prefix",
+		arg: OptionArg::Text("prefix"),
+		help: "Prefix for Article variables in omnispeak files (default: TEXT_).",
+	},
+	CliOption {
+		name: "--omnispeak-terminator-prefix",
+		arg: OptionArg::Text("prefix"),
+		help: "Prefix for Terminator variables in omnispeak files (default: EXTERN_).",
+	},
+	CliOption {
+		name: "--omnispeak-game-ext",
+		arg: OptionArg::Choice(&["yes", "no"]),
+		help: "Whether to set gameExt to the script's extension in omnispeak files (default: yes).",
+	},
+	CliOption {
+		name: "--igrab-header",
+		arg: OptionArg::Filename,
+		help: "Creates a GRAPHEXT/GFXE_EXT C header file.",
+	},
+	CliOption {
+		name: "--igrab-asm",
+		arg: OptionArg::Filename,
+		help: "Creates a GRAPHEXT/GFXE_EXT assembly (.EQU) header.",
+	},
+	CliOption {
+		name: "--igrab-version",
+		arg: OptionArg::Choice(&["0.24", "0.4"]),
+		help: "Emulate the output from a specific IGRAB version.",
+	},
+	CliOption {
+		name: "--timestamps",
+		arg: OptionArg::None,
+		help: "Include the generation time in C/assembly headers (default if built with the\n'timestamps' feature).",
+	},
+	CliOption {
+		name: "--no-timestamps",
+		arg: OptionArg::None,
+		help: "Leave the generation time out of C/assembly headers, so they're reproducible.",
+	},
+	CliOption {
+		name: "--igrab-banner",
+		arg: OptionArg::Text("text"),
+		help: "Use <text> as the first line of the C/assembly header comment.",
+	},
+	CliOption {
+		name: "--igrab-header-title",
+		arg: OptionArg::Text("text"),
+		help: "The same as --igrab-banner.",
+	},
+	CliOption {
+		name: "--igrab-extra-define",
+		arg: OptionArg::TextPair("name", "value"),
+		help: "Add \"#define <name> <value>\" to the end of the C header.\nCan be given more than once.",
+	},
+	CliOption {
+		name: "--igrab-asm-style",
+		arg: OptionArg::Choice(&["tasm", "gas"]),
+		help: "Write assembly headers for TASM/MASM (LABEL = N) or GAS (.set LABEL, N).",
+	},
+	CliOption {
+		name: "--igrab-misc-position",
+		arg: OptionArg::Choice(&["in-enum", "after-amounts"]),
+		help: "Put misc chunks in the graphicnums enum, or #define them after the amounts.",
+	},
+	CliOption {
+		name: "--igrab-annotate-zeros",
+		arg: OptionArg::None,
+		help: "Comment amounts which are 0 with \"(none defined)\" in C headers.",
+	},
+	CliOption {
+		name: "--igrab-c-column",
+		arg: OptionArg::Text("column"),
+		help: "Column to line up IGRAB 0.24 #define values at (default: 41).",
+	},
+	CliOption {
+		name: "--igrab-enum-column",
+		arg: OptionArg::Text("column"),
+		help: "Column to line up IGRAB 0.4 enum comments at (default: 37).",
+	},
+	CliOption {
+		name: "--igrab-enforce-namelen",
+		arg: OptionArg::None,
+		help: "Fail if any names are longer than IGRAB's 32 character limit.",
+	},
+	CliOption {
+		name: "--igrab-allow-invalid-identifiers",
+		arg: OptionArg::None,
+		help: "Write C and assembly headers even if some names aren't valid C identifiers,\nrather than failing.",
+	},
+	CliOption {
+		name: "--igrab-underscore-separator",
+		arg: OptionArg::None,
+		help: "Add an underscore before chunk name suffixes (e.g., _SPR)",
+	},
+	CliOption {
+		name: "--igrab-include-fonts",
+		arg: OptionArg::None,
+		help: "Also #define font and masked font chunks in the C header.",
+	},
+	CliOption {
+		name: "--igrab-gameext-define",
+		arg: OptionArg::None,
+		help: "Add a GAMEEXT string #define with the extension to the C header.",
+	},
+	CliOption {
+		name: "--igrab-no-struct-offsets",
+		arg: OptionArg::None,
+		help: "Don't define STRUCTPIC, STRUCTPICM and STRUCTSPRITE in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-no-numexterns",
+		arg: OptionArg::None,
+		help: "Don't define NUMEXTERNS in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-skip-empty-extern-start",
+		arg: OptionArg::None,
+		help: "Leave out STARTEXTERNS if there are no misc chunks.",
+	},
+	CliOption {
+		name: "--igrab-article-start",
+		arg: OptionArg::None,
+		help: "Define STARTARTICLE (the first Article chunk) in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-b8000text-start",
+		arg: OptionArg::None,
+		help: "Define STARTB800TEXT (the first B8000Text chunk) in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-num-demos",
+		arg: OptionArg::None,
+		help: "Define NUMDEMOSAVES (the number of demos) in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-numlumps",
+		arg: OptionArg::None,
+		help: "Define NUMLUMPS (the number of lumps) in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-no-thank-you",
+		arg: OptionArg::None,
+		help: "Leave out the closing 'Thank you for using idGrab!' comment in C/assembly headers.",
+	},
+	CliOption {
+		name: "--igrab-hybrid",
+		arg: OptionArg::None,
+		help: "Also put the assembly definitions in the C header, inside a comment.",
+	},
+	CliOption {
+		name: "--igrab-pragma-once",
+		arg: OptionArg::None,
+		help: "Start the C header with \"#pragma once\".",
+	},
+	CliOption {
+		name: "--igrab-constexpr",
+		arg: OptionArg::None,
+		help: "Use C23 'constexpr int' instead of #define (IGRAB 0.24 only).",
+	},
+];
+
+pub const COMPLETION_SHELLS: &[&str] = &["bash", "zsh", "fish", "powershell"];
+
+pub const DIFF_FORMATS: &[&str] = &["text", "json", "csv"];
+
+/* Describes the argument(s) an option takes, for when they're missing. */
+pub fn argument_description(option_name: &str) -> String {
+	if option_name == "--diff-format" {
+		return format!("one of {}", DIFF_FORMATS.join(", "));
+	}
+	let option = OPTIONS.iter().find(|option| option.name == option_name);
+	match option.map(|option| &option.arg) {
+		Some(OptionArg::Filename) => "a filename".to_string(),
+		Some(OptionArg::Choice(choices)) => format!("one of {}", choices.join(", ")),
+		Some(OptionArg::Text(name)) => format!("<{}>", name),
+		Some(OptionArg::TextPair(first, second)) => format!("<{}> <{}>", first, second),
+		Some(OptionArg::None) | None => "an argument".to_string(),
+	}
+}
+
+pub fn show_usage() {
+	println!("Usage: idgrab <script> [options]");
+	println!(
+		"       idgrab --completion <{}>",
+		COMPLETION_SHELLS.join(" | ")
+	);
+	println!(
+		"       idgrab --diff <old script> <new script> [--diff-format <{}>]",
+		DIFF_FORMATS.join(" | ")
+	);
+	println!("Scripts ending in .json are read as the JSON which --json writes.");
+	for option in OPTIONS {
+		match option.arg {
+			OptionArg::None => println!("\t{}", option.name),
+			OptionArg::Filename => println!("\t{} <filename>", option.name),
+			OptionArg::Choice(choices) => {
+				println!("\t{} <{}>", option.name, choices.join(" | "))
+			}
+			OptionArg::Text(name) => println!("\t{} <{}>", option.name, name),
+			OptionArg::TextPair(first, second) => {
+				println!("\t{} <{}> <{}>", option.name, first, second)
+			}
+		}
+		for help_line in option.help.lines() {
+			println!("\t\t{}", help_line);
+		}
+	}
+}
+
+/* The first line of an option's help, with characters which upset shell quoting removed. */
+fn short_help(option: &CliOption) -> String {
+	option.help
+		.lines()
+		.next()
+		.unwrap_or("")
+		.chars()
+		.filter(|&c| c != '\'' && c != '[' && c != ']' && c != ':')
+		.collect()
+}
+
+fn write_bash_completion(f: &mut dyn std::io::Write) -> std::io::Result<()> {
+	let all_options: Vec<&str> = OPTIONS.iter().map(|o| o.name).collect();
+	writeln!(f, "# bash completion for idgrab")?;
+	writeln!(f, "_idgrab()")?;
+	writeln!(f, "{{")?;
+	writeln!(f, "\tlocal cur prev")?;
+	writeln!(f, "\tcur=\"${{COMP_WORDS[COMP_CWORD]}}\"")?;
+	writeln!(f, "\tprev=\"${{COMP_WORDS[COMP_CWORD-1]}}\"")?;
+	writeln!(f)?;
+	writeln!(f, "\tcase \"$prev\" in")?;
+	writeln!(f, "\t\t--completion)")?;
+	writeln!(
+		f,
+		"\t\t\tCOMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+		COMPLETION_SHELLS.join(" ")
+	)?;
+	writeln!(f, "\t\t\treturn ;;")?;
+	writeln!(f, "\t\t--diff)")?;
+	writeln!(f, "\t\t\tCOMPREPLY=( $(compgen -f -- \"$cur\") )")?;
+	writeln!(f, "\t\t\treturn ;;")?;
+	writeln!(f, "\t\t--diff-format)")?;
+	writeln!(
+		f,
+		"\t\t\tCOMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+		DIFF_FORMATS.join(" ")
+	)?;
+	writeln!(f, "\t\t\treturn ;;")?;
+	for option in OPTIONS {
+		match option.arg {
+			OptionArg::None => {}
+			OptionArg::Filename => {
+				writeln!(f, "\t\t{})", option.name)?;
+				writeln!(f, "\t\t\tCOMPREPLY=( $(compgen -f -- \"$cur\") )")?;
+				writeln!(f, "\t\t\treturn ;;")?;
+			}
+			OptionArg::Choice(choices) => {
+				writeln!(f, "\t\t{})", option.name)?;
+				writeln!(
+					f,
+					"\t\t\tCOMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+					choices.join(" ")
+				)?;
+				writeln!(f, "\t\t\treturn ;;")?;
+			}
+			OptionArg::Text(_) | OptionArg::TextPair(_, _) => {
+				writeln!(f, "\t\t{})", option.name)?;
+				writeln!(f, "\t\t\tCOMPREPLY=()")?;
+				writeln!(f, "\t\t\treturn ;;")?;
+			}
+		}
+	}
+	writeln!(f, "\tesac")?;
+	writeln!(f)?;
+	writeln!(f, "\tif [ \"$COMP_CWORD\" -eq 1 ]; then")?;
+	writeln!(
+		f,
+		"\t\tCOMPREPLY=( $(compgen -f -- \"$cur\") $(compgen -W \"--completion --diff\" -- \"$cur\") )"
+	)?;
+	writeln!(f, "\telif [[ \"$cur\" == -* ]]; then")?;
+	writeln!(
+		f,
+		"\t\tCOMPREPLY=( $(compgen -W \"{}\" -- \"$cur\") )",
+		all_options.join(" ")
+	)?;
+	writeln!(f, "\tfi")?;
+	writeln!(f, "}}")?;
+	writeln!(f, "complete -o filenames -F _idgrab idgrab")
+}
+
+fn write_zsh_completion(f: &mut dyn std::io::Write) -> std::io::Result<()> {
+	writeln!(f, "#compdef idgrab")?;
+	writeln!(f)?;
+	writeln!(f, "_idgrab() {{")?;
+	writeln!(f, "\tif (( CURRENT == 2 )); then")?;
+	writeln!(
+		f,
+		"\t\t_alternative 'files:script:_files' 'modes:mode:(--completion --diff)'"
+	)?;
+	writeln!(f, "\t\treturn")?;
+	writeln!(f, "\tfi")?;
+	writeln!(f, "\tif [[ ${{words[2]}} == --diff ]]; then")?;
+	writeln!(
+		f,
+		"\t\t_arguments '--diff-format[Output format]:format:({})' '*:script:_files'",
+		DIFF_FORMATS.join(" ")
+	)?;
+	writeln!(f, "\t\treturn")?;
+	writeln!(f, "\tfi")?;
+	writeln!(f, "\tif [[ ${{words[2]}} == --completion ]]; then")?;
+	writeln!(f, "\t\t_values 'shell' {}", COMPLETION_SHELLS.join(" "))?;
+	writeln!(f, "\t\treturn")?;
+	writeln!(f, "\tfi")?;
+	writeln!(f, "\t_arguments -s \\")?;
+	writeln!(f, "\t\t'1:script:_files' \\")?;
+	for option in OPTIONS {
+		let arg_spec = match option.arg {
+			OptionArg::None => String::new(),
+			OptionArg::Filename => ":filename:_files".to_string(),
+			OptionArg::Choice(choices) => format!(":value:({})", choices.join(" ")),
+			OptionArg::Text(name) => format!(":{}: ", name),
+			OptionArg::TextPair(first, second) => format!(":{}: :{}: ", first, second),
+		};
+		writeln!(
+			f,
+			"\t\t'*{}[{}]{}' \\",
+			option.name,
+			short_help(option),
+			arg_spec
+		)?;
+	}
+	writeln!(f)?;
+	writeln!(f, "}}")?;
+	writeln!(f)?;
+	writeln!(f, "_idgrab \"$@\"")
+}
+
+fn write_fish_completion(f: &mut dyn std::io::Write) -> std::io::Result<()> {
+	writeln!(f, "# fish completion for idgrab")?;
+	writeln!(
+		f,
+		"complete -c idgrab -n '__fish_is_first_arg' -l completion -x -a '{}' -d 'Print a shell completion script'",
+		COMPLETION_SHELLS.join(" ")
+	)?;
+	writeln!(
+		f,
+		"complete -c idgrab -n '__fish_is_first_arg' -l diff -r -F -d 'Compare the chunks in two scripts'"
+	)?;
+	writeln!(
+		f,
+		"complete -c idgrab -l diff-format -x -a '{}' -d 'Output format for --diff'",
+		DIFF_FORMATS.join(" ")
+	)?;
+	for option in OPTIONS {
+		let long_name = option.name.trim_start_matches("--");
+		let arg_spec = match option.arg {
+			OptionArg::None => String::new(),
+			OptionArg::Filename => " -r -F".to_string(),
+			OptionArg::Choice(choices) => format!(" -x -a '{}'", choices.join(" ")),
+			OptionArg::Text(_) | OptionArg::TextPair(_, _) => " -x".to_string(),
+		};
+		writeln!(
+			f,
+			"complete -c idgrab -l {}{} -d '{}'",
+			long_name,
+			arg_spec,
+			short_help(option)
+		)?;
+	}
+	Ok(())
+}
+
+fn write_powershell_completion(f: &mut dyn std::io::Write) -> std::io::Result<()> {
+	writeln!(f, "# PowerShell completion for idgrab")?;
+	writeln!(
+		f,
+		"Register-ArgumentCompleter -Native -CommandName idgrab -ScriptBlock {{"
+	)?;
+	writeln!(f, "\tparam($wordToComplete, $commandAst, $cursorPosition)")?;
+	writeln!(f)?;
+	writeln!(f, "\t$prev = $null")?;
+	writeln!(f, "\tforeach ($element in $commandAst.CommandElements) {{")?;
+	writeln!(
+		f,
+		"\t\tif ($element.Extent.EndOffset -lt $cursorPosition) {{"
+	)?;
+	writeln!(f, "\t\t\t$prev = $element.ToString()")?;
+	writeln!(f, "\t\t}}")?;
+	writeln!(f, "\t}}")?;
+	writeln!(f)?;
+	writeln!(f, "\t$values = switch ($prev) {{")?;
+	writeln!(
+		f,
+		"\t\t'--completion' {{ @({}) }}",
+		quoted_list(COMPLETION_SHELLS)
+	)?;
+	writeln!(
+		f,
+		"\t\t'--diff-format' {{ @({}) }}",
+		quoted_list(DIFF_FORMATS)
+	)?;
+	writeln!(f, "\t\t'--diff' {{ return }}")?;
+	for option in OPTIONS {
+		match option.arg {
+			OptionArg::None => {}
+			OptionArg::Choice(choices) => {
+				writeln!(
+					f,
+					"\t\t'{}' {{ @({}) }}",
+					option.name,
+					quoted_list(choices)
+				)?;
+			}
+			/* Returning nothing falls back to PowerShell's own (file) completion. */
+			OptionArg::Filename | OptionArg::Text(_) | OptionArg::TextPair(_, _) => {
+				writeln!(f, "\t\t'{}' {{ return }}", option.name)?;
+			}
+		}
+	}
+	let all_options: Vec<&str> = OPTIONS.iter().map(|o| o.name).collect();
+	writeln!(f, "\t\tdefault {{")?;
+	writeln!(f, "\t\t\tif ($wordToComplete -notlike '-*') {{ return }}")?;
+	writeln!(f, "\t\t\t@({})", quoted_list(&all_options))?;
+	writeln!(f, "\t\t}}")?;
+	writeln!(f, "\t}}")?;
+	writeln!(f)?;
+	writeln!(
+		f,
+		"\t$values | Where-Object {{ $_ -like \"$wordToComplete*\" }} | ForEach-Object {{"
+	)?;
+	writeln!(
+		f,
+		"\t\t[System.Management.Automation.CompletionResult]::new($_, $_, 'ParameterValue', $_)"
+	)?;
+	writeln!(f, "\t}}")?;
+	writeln!(f, "}}")
+}
+
+fn quoted_list(values: &[&str]) -> String {
+	values.iter()
+		.map(|v| format!("'{}'", v))
+		.collect::<Vec<String>>()
+		.join(", ")
+}
+
+/* Writes a completion script for the given shell, or returns false if it's unsupported. */
+pub fn write_completion(shell: &str, f: &mut dyn std::io::Write) -> std::io::Result<bool> {
+	match shell {
+		"bash" => write_bash_completion(f)?,
+		"zsh" => write_zsh_completion(f)?,
+		"fish" => write_fish_completion(f)?,
+		"powershell" => write_powershell_completion(f)?,
+		_ => return Ok(false),
+	}
+	Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn completion_string(shell: &str) -> String {
+		let mut out = Vec::new();
+		assert!(write_completion(shell, &mut out).unwrap());
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn argument_descriptions() {
+		assert_eq!(argument_description("--gfxinfo"), "a filename");
+		assert_eq!(argument_description("--igrab-version"), "one of 0.24, 0.4");
+		assert_eq!(argument_description("--igrab-c-column"), "<column>");
+		assert_eq!(
+			argument_description("--igrab-extra-define"),
+			"<name> <value>"
+		);
+	}
+
+	#[test]
+	fn completions_cover_all_options() {
+		for shell in COMPLETION_SHELLS {
+			let script = completion_string(shell);
+			for option in OPTIONS {
+				let name = if *shell == "fish" {
+					format!("-l {}", option.name.trim_start_matches("--"))
+				} else {
+					option.name.to_string()
+				};
+				assert!(
+					script.contains(&name),
+					"{} missing from {}",
+					option.name,
+					shell
+				);
+			}
+		}
+	}
+
+	#[test]
+	fn completions_list_igrab_versions() {
+		assert!(completion_string("bash")
+			.contains("--igrab-version)\n\t\t\tCOMPREPLY=( $(compgen -W \"0.24 0.4\" -- \"$cur\") )"));
+		assert!(completion_string("zsh").contains(":value:(0.24 0.4)'"));
+		assert!(completion_string("fish").contains("-l igrab-version -x -a '0.24 0.4'"));
+		assert!(completion_string("powershell")
+			.contains("'--igrab-version' { @('0.24', '0.4') }"));
+	}
+
+	#[test]
+	fn completion_unknown_shell() {
+		let mut out = Vec::new();
+		assert!(!write_completion("tcsh", &mut out).unwrap());
+		assert!(out.is_empty());
+	}
+}
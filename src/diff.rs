@@ -0,0 +1,345 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+use json::json_escape;
+use std::collections::HashMap;
+use GfxHeaders;
+use MiscChunk;
+
+#[derive(PartialEq, Debug)]
+pub enum ChunkDiffKind {
+	Added,
+	Removed,
+	/* A different name at the same chunk number. */
+	Renamed,
+	/* The same name at a different chunk number. */
+	Moved,
+}
+
+impl ChunkDiffKind {
+	fn name(&self) -> &'static str {
+		match self {
+			ChunkDiffKind::Added => "added",
+			ChunkDiffKind::Removed => "removed",
+			ChunkDiffKind::Renamed => "renamed",
+			ChunkDiffKind::Moved => "moved",
+		}
+	}
+}
+
+/* A single named chunk which differs between two scripts. */
+#[derive(PartialEq, Debug)]
+pub struct ChunkDiff {
+	pub kind: ChunkDiffKind,
+	pub name: String,
+	/* The name in the old script, if it was renamed. */
+	pub old_name: Option<String>,
+	pub old_id: Option<u32>,
+	pub new_id: Option<u32>,
+}
+
+#[derive(PartialEq, Clone, Copy)]
+pub enum DiffFormat {
+	Text,
+	Json,
+	Csv,
+}
+
+impl DiffFormat {
+	pub fn from_name(name: &str) -> Option<DiffFormat> {
+		match name {
+			"text" => Some(DiffFormat::Text),
+			"json" => Some(DiffFormat::Json),
+			"csv" => Some(DiffFormat::Csv),
+			_ => None,
+		}
+	}
+}
+
+impl GfxHeaders {
+	/* The (IGRAB-style) name and chunk number of every named chunk. */
+	pub fn named_chunks(&self) -> Vec<(String, u32)> {
+		let mut chunks = Vec::new();
+		let fonts = self.fonts.iter().chain(&self.fonts_masked);
+		for (chunk_id, font) in (self.fonts_start()..).zip(fonts) {
+			chunks.push((font.clone(), chunk_id));
+		}
+		for (chunk_id, pic) in (self.bitmaps_start()..).zip(&self.bitmaps) {
+			chunks.push((format!("{}PIC", pic), chunk_id));
+		}
+		for (chunk_id, picm) in (self.bitmaps_masked_start()..).zip(&self.bitmaps_masked) {
+			chunks.push((format!("{}PICM", picm), chunk_id));
+		}
+		for (chunk_id, sprite) in (self.sprites_start()..).zip(&self.sprites) {
+			chunks.push((format!("{}SPR", sprite), chunk_id));
+		}
+		for (chunk_id, misc) in (self.misc_start()..).zip(&self.misc_chunks) {
+			let name = match misc {
+				MiscChunk::Chunk(name)
+				| MiscChunk::Article(name)
+				| MiscChunk::B8000Text(name)
+				| MiscChunk::Terminator(name) => name.clone(),
+				MiscChunk::Demo(num) => format!("DEMO{}", num),
+			};
+			chunks.push((name, chunk_id));
+		}
+		chunks
+	}
+
+	/*
+	 * Compares the named chunks in two scripts. A chunk whose name is only in
+	 * one script is treated as renamed if the other script has a different
+	 * (also unmatched) name at the same chunk number.
+	 */
+	pub fn diff(&self, new: &GfxHeaders) -> Vec<ChunkDiff> {
+		let old_chunks = self.named_chunks();
+		let new_chunks = new.named_chunks();
+		let old_ids: HashMap<&str, u32> = old_chunks
+			.iter()
+			.map(|(name, id)| (name.as_str(), *id))
+			.collect();
+		let new_ids: HashMap<&str, u32> = new_chunks
+			.iter()
+			.map(|(name, id)| (name.as_str(), *id))
+			.collect();
+
+		let mut removed: HashMap<u32, &str> = old_chunks
+			.iter()
+			.filter(|(name, _)| !new_ids.contains_key(name.as_str()))
+			.map(|(name, id)| (*id, name.as_str()))
+			.collect();
+
+		let mut diffs = Vec::new();
+		for (name, new_id) in &new_chunks {
+			match old_ids.get(name.as_str()) {
+				Some(old_id) if old_id != new_id => diffs.push(ChunkDiff {
+					kind: ChunkDiffKind::Moved,
+					name: name.clone(),
+					old_name: None,
+					old_id: Some(*old_id),
+					new_id: Some(*new_id),
+				}),
+				Some(_) => {}
+				None => {
+					if let Some(old_name) = removed.remove(new_id) {
+						diffs.push(ChunkDiff {
+							kind: ChunkDiffKind::Renamed,
+							name: name.clone(),
+							old_name: Some(old_name.to_string()),
+							old_id: Some(*new_id),
+							new_id: Some(*new_id),
+						});
+					} else {
+						diffs.push(ChunkDiff {
+							kind: ChunkDiffKind::Added,
+							name: name.clone(),
+							old_name: None,
+							old_id: None,
+							new_id: Some(*new_id),
+						});
+					}
+				}
+			}
+		}
+		for (old_id, name) in removed {
+			diffs.push(ChunkDiff {
+				kind: ChunkDiffKind::Removed,
+				name: name.to_string(),
+				old_name: None,
+				old_id: Some(old_id),
+				new_id: None,
+			});
+		}
+		diffs.sort_by_key(|d| (d.new_id.or(d.old_id), d.old_id));
+		diffs
+	}
+}
+
+pub fn write_diff(
+	f: &mut dyn std::io::Write,
+	diffs: &[ChunkDiff],
+	format: DiffFormat,
+) -> std::io::Result<()> {
+	let id_str = |id: Option<u32>| id.map(|id| id.to_string()).unwrap_or_default();
+	match format {
+		DiffFormat::Text => {
+			for diff in diffs {
+				match diff.kind {
+					ChunkDiffKind::Added => writeln!(
+						f,
+						"+ {} = {}",
+						diff.name,
+						id_str(diff.new_id)
+					)?,
+					ChunkDiffKind::Removed => writeln!(
+						f,
+						"- {} = {}",
+						diff.name,
+						id_str(diff.old_id)
+					)?,
+					ChunkDiffKind::Renamed => writeln!(
+						f,
+						"~ {} (was {}) @ {}",
+						diff.name,
+						diff.old_name.as_ref().unwrap(),
+						id_str(diff.new_id)
+					)?,
+					ChunkDiffKind::Moved => writeln!(
+						f,
+						"> {} = {} (was {})",
+						diff.name,
+						id_str(diff.new_id),
+						id_str(diff.old_id)
+					)?,
+				}
+			}
+		}
+		DiffFormat::Json => {
+			writeln!(f, "[")?;
+			let mut diff_iterator = diffs.iter().peekable();
+			while let Some(diff) = diff_iterator.next() {
+				write!(
+					f,
+					"\t{{ \"kind\": \"{}\", \"name\": \"{}\"",
+					diff.kind.name(),
+					json_escape(&diff.name)
+				)?;
+				if let Some(old_name) = &diff.old_name {
+					write!(f, ", \"old_name\": \"{}\"", json_escape(old_name))?;
+				}
+				match diff.kind {
+					ChunkDiffKind::Added | ChunkDiffKind::Renamed => write!(
+						f,
+						", \"chunk_id\": {}",
+						id_str(diff.new_id)
+					)?,
+					ChunkDiffKind::Removed => write!(
+						f,
+						", \"chunk_id\": {}",
+						id_str(diff.old_id)
+					)?,
+					ChunkDiffKind::Moved => write!(
+						f,
+						", \"chunk_id\": {}, \"old_chunk_id\": {}",
+						id_str(diff.new_id),
+						id_str(diff.old_id)
+					)?,
+				}
+				let comma = if diff_iterator.peek().is_none() {
+					""
+				} else {
+					","
+				};
+				writeln!(f, " }}{}", comma)?;
+			}
+			writeln!(f, "]")?;
+		}
+		DiffFormat::Csv => {
+			writeln!(f, "kind,name,old_id,new_id")?;
+			for diff in diffs {
+				writeln!(
+					f,
+					"{},{},{},{}",
+					diff.kind.name(),
+					csv_field(&diff.name),
+					id_str(diff.old_id),
+					id_str(diff.new_id)
+				)?;
+			}
+		}
+	}
+	Ok(())
+}
+
+/* Quotes a CSV field if it has anything which would split it, doubling any '"'s. */
+fn csv_field(s: &str) -> String {
+	if s.contains(&[',', '"', '\n', '\r'][..]) {
+		format!("\"{}\"", s.replace('"', "\"\""))
+	} else {
+		s.to_string()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parse_gfx_script_str;
+
+	fn diff_string(old: &str, new: &str, format: DiffFormat) -> String {
+		let diffs = parse_gfx_script_str(old)
+			.unwrap()
+			.diff(&parse_gfx_script_str(new).unwrap());
+		let mut out = Vec::new();
+		write_diff(&mut out, &diffs, format).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	const OLD: &str =
+		"Bitmaps {\n\t\"TITLE\"\n\t\"OLDNAME\"\n\t\"GONE\"\n}\nSprites {\n\t\"KEEN\"\n}\n";
+	const NEW: &str =
+		"Bitmaps {\n\t\"TITLE\"\n\t\"RENAMED\"\n}\nSprites {\n\t\"KEEN\"\n\t\"SHOT\"\n}\n";
+
+	#[test]
+	fn diff_text() {
+		assert_eq!(
+			diff_string(OLD, NEW, DiffFormat::Text),
+			"~ RENAMEDPIC (was OLDNAMEPIC) @ 4\n\
+			 - GONEPIC = 5\n\
+			 > KEENSPR = 5 (was 6)\n\
+			 + SHOTSPR = 6\n"
+		);
+		assert_eq!(diff_string(OLD, OLD, DiffFormat::Text), "");
+	}
+
+	#[test]
+	fn diff_json_and_csv() {
+		let json = diff_string(OLD, NEW, DiffFormat::Json);
+		assert!(json.starts_with("[\n"));
+		assert!(json.contains(
+			"\t{ \"kind\": \"added\", \"name\": \"SHOTSPR\", \"chunk_id\": 6 }\n]"
+		));
+		assert!(json.contains(
+			"{ \"kind\": \"renamed\", \"name\": \"RENAMEDPIC\", \"old_name\": \"OLDNAMEPIC\", \"chunk_id\": 4 },"
+		));
+
+		let csv = diff_string(OLD, NEW, DiffFormat::Csv);
+		assert!(csv.starts_with("kind,name,old_id,new_id\n"));
+		assert!(csv.contains("removed,GONEPIC,5,\n"));
+		assert!(csv.contains("moved,KEENSPR,6,5\n"));
+
+		/* Names can have anything in them, so may need quoting. */
+		let csv = diff_string(
+			"Bitmaps {\n\t\"A,B\"\n}\n",
+			"Bitmaps {\n\t\"SAY\\\"HI\"\n}\n",
+			DiffFormat::Csv,
+		);
+		assert_eq!(
+			csv,
+			"kind,name,old_id,new_id\nrenamed,\"SAY\"\"HIPIC\",3,3\n"
+		);
+		let csv = diff_string(
+			"Bitmaps {\n\t\"A\"\n}\n",
+			"Bitmaps {\n\t\"A\"\n\t\"B,C\"\n}\n",
+			DiffFormat::Csv,
+		);
+		assert!(csv.ends_with("\nadded,\"B,CPIC\",,4\n"));
+	}
+}
@@ -22,16 +22,16 @@
 // The tab width used in outputting IGRAB files. Mostly used by 0.24
 const IGRAB_TAB_WIDTH: usize = 8;
 
-#[derive(PartialEq, Clone, Copy)]
+// IGRAB's maximum name length. Longer names were truncated.
+pub const IGRAB_NAMELEN: usize = 32;
+
+#[derive(PartialEq, Clone, Copy, Default)]
 pub enum IGrabVersion {
 	ZeroPointTwoFour,
+	#[default]
 	ZeroPointFour,
 }
 
-impl Default for IGrabVersion {
-	fn default() -> IGrabVersion { IGrabVersion::ZeroPointFour }
-}
-
 impl std::fmt::Display for IGrabVersion {
 	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
 		match self {
@@ -41,13 +41,158 @@ impl std::fmt::Display for IGrabVersion {
 	}
 }
 
-#[derive(Default)]
+/* The syntax used for assembly (.EQU) header definitions. */
+#[derive(PartialEq, Clone, Copy, Default)]
+pub enum AsmEqualsStyle {
+	/* TASM/MASM: "LABEL\t=\tVALUE" */
+	#[default]
+	Tasm,
+	/* GNU as: ".set LABEL, VALUE" */
+	Gas,
+}
+
+/* Where the misc chunks (externs) go in C headers. */
+#[derive(PartialEq, Clone, Copy, Default)]
+pub enum MiscPosition {
+	/* In the graphicnums enum, after the sprites (IGRAB 0.4 only). */
+	#[default]
+	InEnum,
+	/* As #defines after the amounts, like some of the original Keen headers. */
+	AfterAmounts,
+}
+
+/* Escapes a string for use inside a C string literal. */
+pub fn c_string_escape(s: &str) -> String {
+	let mut escaped = String::new();
+	for c in s.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			'\n' => escaped.push_str("\\n"),
+			'\t' => escaped.push_str("\\t"),
+			c if c.is_ascii_graphic() || c == ' ' => escaped.push(c),
+			c => {
+				let mut buf = [0u8; 4];
+				for byte in c.encode_utf8(&mut buf).bytes() {
+					escaped.push_str(&format!("\\{:03o}", byte));
+				}
+			}
+		}
+	}
+	escaped
+}
+
+#[derive(Clone)]
 pub struct IGrabOptions {
 	pub version: IGrabVersion,
 	pub append_underscores: bool,
+	/* Emit C23 constexpr ints instead of #defines (0.24 mode only). */
+	pub use_constexpr: bool,
+	/* Emit #defines for fonts and masked fonts, which IGRAB never did. */
+	pub emit_font_defines: bool,
+	/* Emit #define GAMEEXT "EXT" if the script has an extension. */
+	pub emit_gameext_define: bool,
+	pub asm_equals_style: AsmEqualsStyle,
+	pub misc_position: MiscPosition,
+	/* The column values are aligned to in 0.24-style #defines. */
+	pub c_column: usize,
+	/* The column the "// N" comments are aligned to in the 0.4 enum. */
+	pub enum_column: usize,
+	/*
+	 * Replaces the "Graphics .H file for EXT" line in the header comment,
+	 * e.g. with "GRAPHEXT.H for Keen 4". Set by --igrab-banner or
+	 * --igrab-header-title.
+	 */
+	pub custom_banner: Option<String>,
+	/* Emit the STRUCTPIC/STRUCTPICM/STRUCTSPRITE definitions. */
+	pub emit_struct_offsets: bool,
+	/* Emit NUMEXTERNS (the number of misc chunks) with the other amounts. */
+	pub emit_numexterns: bool,
+	/* Note when the header was generated. On by default with the "timestamps" feature. */
+	pub timestamps: bool,
+	/* Also include the assembly definitions, commented out, in the C header. */
+	pub hybrid_c_asm: bool,
+	/* Start the C header with "#pragma once", before the banner comment. */
+	pub pragma_once: bool,
+	/* Leave out STARTEXTERNS if there are no misc chunks for it to point to. */
+	pub skip_empty_extern_start: bool,
+	/* Emit STARTARTICLE (the first Article chunk), if there are any. */
+	pub emit_article_start: bool,
+	/* Emit STARTB800TEXT (the first B8000Text chunk), if there are any. */
+	pub emit_b8000text_start: bool,
+	/* Emit NUMDEMOSAVES (the number of demos), if there are any. */
+	pub emit_num_demos: bool,
+	/* Add a "// (none defined)" comment to amounts which are 0. */
+	pub annotate_zero_counts: bool,
+	/* Emit NUMLUMPS (the number of lumps), which Keen itself doesn't use. */
+	pub emit_numlumps: bool,
+	/* Extra "#define NAME VALUE" lines to put at the end of the C header. */
+	pub extra_defines: Vec<(String, String)>,
+	/* Leave out the closing "Thank you for using idGrab!" comment. */
+	pub suppress_thank_you: bool,
+}
+
+impl Default for IGrabOptions {
+	fn default() -> IGrabOptions {
+		IGrabOptions {
+			version: IGrabVersion::default(),
+			append_underscores: false,
+			use_constexpr: false,
+			emit_font_defines: false,
+			emit_gameext_define: false,
+			asm_equals_style: AsmEqualsStyle::default(),
+			misc_position: MiscPosition::default(),
+			c_column: 41, /* "#define ".len() + 33 */
+			enum_column: IGRAB_NAMELEN + 5,
+			custom_banner: None,
+			emit_struct_offsets: true,
+			emit_numexterns: true,
+			timestamps: cfg!(feature = "timestamps"),
+			hybrid_c_asm: false,
+			pragma_once: false,
+			skip_empty_extern_start: false,
+			emit_article_start: false,
+			emit_b8000text_start: false,
+			emit_num_demos: false,
+			annotate_zero_counts: false,
+			emit_numlumps: false,
+			extra_defines: Vec::new(),
+			suppress_thank_you: false,
+		}
+	}
 }
 
 impl IGrabOptions {
+	/* Builds the full identifier for a chunk, e.g. TITLESCREEN + PIC -> TITLESCREENPIC. */
+	pub fn full_chunk_name(&self, chunk_name: &str, chunk_suffix: Option<&str>) -> String {
+		match chunk_suffix {
+			Some(suffix) if self.append_underscores => {
+				format!("{}_{}", chunk_name, suffix)
+			}
+			Some(suffix) => format!("{}{}", chunk_name, suffix),
+			None => chunk_name.to_string(),
+		}
+	}
+
+	/* Writes an IGRAB 0.24-style, tab-aligned #define, whatever the version. */
+	pub fn write_define_line(
+		&self,
+		f: &mut dyn std::io::Write,
+		chunk_name: &str,
+		chunk_suffix: Option<&str>,
+		chunk_num: u32,
+	) -> std::io::Result<()> {
+		let full_name = self.full_chunk_name(chunk_name, chunk_suffix);
+		let num_chars = 8 + full_name.len();
+		// Names which reach the column still need a tab to separate the value.
+		let num_tabs = (self.c_column.saturating_sub(num_chars) / IGRAB_TAB_WIDTH).max(1);
+		write!(f, "#define {}", full_name)?;
+		for _ in 0..num_tabs {
+			write!(f, "\t")?;
+		}
+		writeln!(f, "{}", chunk_num)
+	}
+
 	pub fn write_chunk_line(
 		&self,
 		f: &mut dyn std::io::Write,
@@ -56,73 +201,23 @@ impl IGrabOptions {
 		chunk_num: u32,
 		first: bool,
 	) -> std::io::Result<()> {
+		let full_name = self.full_chunk_name(chunk_name, chunk_suffix);
 		match self.version {
+			IGrabVersion::ZeroPointTwoFour if self.use_constexpr => {
+				writeln!(f, "constexpr int {} = {};", full_name, chunk_num)
+			}
 			IGrabVersion::ZeroPointTwoFour => {
-				let num_chars = 8
-					+ chunk_name.len() + if self.append_underscores
-					&& chunk_suffix != None
-				{
-					1
-				} else {
-					0
-				} + if chunk_suffix != None {
-					chunk_suffix.unwrap().len()
-				} else {
-					0
-				};
-				let desired_column = 41; /* "#define ".len() */
-				let num_tabs = (desired_column - num_chars) / IGRAB_TAB_WIDTH;
-				write!(f, "#define {}", chunk_name)?;
-				if self.append_underscores && chunk_suffix != None {
-					write!(f, "_")?;
-				}
-				if chunk_suffix != None {
-					write!(f, "{}", chunk_suffix.unwrap())?;
-				}
-				for _ in 0..num_tabs {
-					write!(f, "\t")?;
-				}
-				writeln!(f, "{}", chunk_num)
+				self.write_define_line(f, chunk_name, chunk_suffix, chunk_num)
 			}
 			IGrabVersion::ZeroPointFour => {
 				if first {
-					writeln!(
-						f,
-						"\t\t{}{}{} = {},",
-						chunk_name,
-						if self.append_underscores && chunk_suffix != None {
-							"_"
-						} else {
-							""
-						},
-						chunk_suffix.unwrap_or(""),
-						chunk_num
-					)
+					writeln!(f, "\t\t{} = {},", full_name, chunk_num)
 				} else {
-					let num_chars = chunk_name.len()
-						+ if self.append_underscores && chunk_suffix != None
-						{
-							1
-						} else {
-							0
-						} + if chunk_suffix != None {
-						chunk_suffix.unwrap().len()
-					} else {
-						0
-					} + 1; // ','
-					let desired_column = 32 + 5; /* NAMELEN + 5 */
-					let num_spaces = desired_column - num_chars;
-					write!(
-						f,
-						"\t\t{}{}{},",
-						chunk_name,
-						if self.append_underscores && chunk_suffix != None {
-							"_"
-						} else {
-							""
-						},
-						chunk_suffix.unwrap_or("")
-					)?;
+					let num_chars = full_name.len() + 1; /* ',' */
+					/* Always leave at least one space before the comment. */
+					let num_spaces =
+						self.enum_column.saturating_sub(num_chars).max(1);
+					write!(f, "\t\t{},", full_name)?;
 					for _ in 0..num_spaces {
 						write!(f, " ")?;
 					}
@@ -132,6 +227,30 @@ impl IGrabOptions {
 		}
 	}
 
+	/* The comment character for the assembler being targeted. */
+	pub fn asm_comment(&self) -> char {
+		match self.asm_equals_style {
+			AsmEqualsStyle::Tasm => ';',
+			AsmEqualsStyle::Gas => '#',
+		}
+	}
+
+	/*
+	 * Writes a fixed assembly definition. The label may include trailing
+	 * spaces, which are kept to match IGRAB's TASM output.
+	 */
+	pub fn write_asm_equ(
+		&self,
+		f: &mut dyn std::io::Write,
+		label: &str,
+		value: impl std::fmt::Display,
+	) -> std::io::Result<()> {
+		match self.asm_equals_style {
+			AsmEqualsStyle::Tasm => writeln!(f, "{}\t=\t{}", label, value),
+			AsmEqualsStyle::Gas => writeln!(f, ".set {}, {}", label.trim_end(), value),
+		}
+	}
+
 	pub fn write_asm_chunk_line(
 		&self,
 		f: &mut dyn std::io::Write,
@@ -139,29 +258,103 @@ impl IGrabOptions {
 		chunk_suffix: Option<&str>,
 		chunk_num: u32,
 	) -> std::io::Result<()> {
-		let num_chars = chunk_name.len()
-			+ if chunk_suffix != None {
-				chunk_suffix.unwrap().len()
-					+ if self.append_underscores { 1 } else { 0 }
-			} else {
-				0
-			};
+		let full_name = self.full_chunk_name(chunk_name, chunk_suffix);
+		if self.asm_equals_style == AsmEqualsStyle::Gas {
+			return writeln!(f, ".set {}, {}", full_name, chunk_num);
+		}
+		let num_chars = full_name.len();
 		let desired_column = 33;
-		let num_tabs = (desired_column - num_chars + IGRAB_TAB_WIDTH - 2) / IGRAB_TAB_WIDTH;
-		write!(
-			f,
-			"{}{}{}",
-			chunk_name,
-			if self.append_underscores && chunk_suffix != None {
-				"_"
-			} else {
-				""
-			},
-			chunk_suffix.unwrap_or("")
-		)?;
+		// Names past the column get no padding, but "\t=\t" still separates them.
+		let num_tabs = (desired_column + IGRAB_TAB_WIDTH - 2).saturating_sub(num_chars)
+			/ IGRAB_TAB_WIDTH;
+		write!(f, "{}", full_name)?;
 		for _ in 0..num_tabs {
 			write!(f, "\t")?;
 		}
 		writeln!(f, "\t=\t{}", chunk_num)
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn chunk_line_string(options: &IGrabOptions, name: &str, suffix: Option<&str>) -> String {
+		let mut out = Vec::new();
+		options.write_chunk_line(&mut out, name, suffix, 42, false)
+			.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn chunk_line_long_name() {
+		let options = IGrabOptions::default();
+		let name = "A".repeat(40);
+		assert_eq!(
+			chunk_line_string(&options, &name, Some("PIC")),
+			format!("\t\t{}PIC, // 42\n", name)
+		);
+		/* 36 characters plus the comma land exactly on the comment column. */
+		let name = "B".repeat(33);
+		assert_eq!(
+			chunk_line_string(&options, &name, Some("PIC")),
+			format!("\t\t{}PIC, // 42\n", name)
+		);
+	}
+
+	#[test]
+	fn define_line_long_name() {
+		let options = IGrabOptions {
+			version: IGrabVersion::ZeroPointTwoFour,
+			..Default::default()
+		};
+		let name = "BIGFLAGWAVINGANIMATIONFRAMENUMBER";
+		assert_eq!(
+			chunk_line_string(&options, name, Some("SPR")),
+			format!("#define {}SPR\t42\n", name)
+		);
+		/* Names ending just before the column still get a tab. */
+		let name = "A".repeat(30);
+		assert_eq!(
+			chunk_line_string(&options, &name, Some("PIC")),
+			format!("#define {}PIC\t42\n", name)
+		);
+	}
+
+	#[test]
+	fn custom_columns() {
+		let mut options = IGrabOptions {
+			enum_column: 20,
+			..Default::default()
+		};
+		assert_eq!(
+			chunk_line_string(&options, "TITLE", Some("PIC")),
+			"\t\tTITLEPIC,           // 42\n"
+		);
+
+		options.version = IGrabVersion::ZeroPointTwoFour;
+		assert_eq!(
+			chunk_line_string(&options, "TITLE", Some("PIC")),
+			"#define TITLEPIC\t\t\t42\n"
+		);
+		options.c_column = 33;
+		assert_eq!(
+			chunk_line_string(&options, "TITLE", Some("PIC")),
+			"#define TITLEPIC\t\t42\n"
+		);
+	}
+
+	#[test]
+	fn asm_chunk_line_alignment() {
+		let options = IGrabOptions::default();
+		let asm_line = |name: &str| {
+			let mut out = Vec::new();
+			options.write_asm_chunk_line(&mut out, name, Some("PIC"), 7)
+				.unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		assert_eq!(asm_line("A"), "APIC\t\t\t\t\t=\t7\n");
+		let name = "A".repeat(40);
+		assert_eq!(asm_line(&name), format!("{}PIC\t=\t7\n", name));
+	}
+}
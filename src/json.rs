@@ -0,0 +1,1023 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+use create_output_file;
+use duplicate_name_message;
+use parser::{ParseError, ParseResult};
+use std::collections::HashMap;
+use std::io::Write;
+use std::path::Path;
+use ChunkSection;
+use GameVariant;
+use GfxHeaders;
+use Lump;
+use MiscChunk;
+
+pub fn json_escape(s: &str) -> String {
+	let mut escaped = String::new();
+	for c in s.chars() {
+		match c {
+			'"' => escaped.push_str("\\\""),
+			'\\' => escaped.push_str("\\\\"),
+			c if (c as u32) < 0x20 => escaped.push_str(&format!("\\u{:04x}", c as u32)),
+			c => escaped.push(c),
+		}
+	}
+	escaped
+}
+
+fn json_string(s: &str) -> String {
+	format!("\"{}\"", json_escape(s))
+}
+
+/* Writes one element per line, indented one level past the key. */
+fn json_array(elements: &[String]) -> String {
+	if elements.is_empty() {
+		return "[]".to_string();
+	}
+	format!("[\n\t\t{}\n\t]", elements.join(",\n\t\t"))
+}
+
+fn json_name_array(names: &[String]) -> String {
+	let elements: Vec<String> = names.iter().map(|name| json_string(name)).collect();
+	json_array(&elements)
+}
+
+pub fn section_json_name(section: ChunkSection) -> &'static str {
+	match section {
+		ChunkSection::Fonts => "fonts",
+		ChunkSection::FontsMasked => "fonts_masked",
+		ChunkSection::Bitmaps => "bitmaps",
+		ChunkSection::BitmapsMasked => "bitmaps_masked",
+		ChunkSection::Sprites => "sprites",
+		ChunkSection::Misc => "misc_chunks",
+	}
+}
+
+impl GfxHeaders {
+	/*
+	 * Writes everything the script defined as a JSON object. Misc chunks are
+	 * tagged with a "type", and lumps have the chunks they resolved to.
+	 */
+	pub fn write_json(&self, f: &mut dyn Write) -> std::io::Result<()> {
+		let optional_names = |names: &Option<Vec<String>>| match names {
+			Some(names) => json_name_array(names),
+			None => "null".to_string(),
+		};
+		let misc_chunks: Vec<String> = self
+			.misc_chunks
+			.iter()
+			.map(|chunk| match chunk {
+				MiscChunk::Chunk(name) => {
+					format!(
+						"{{ \"type\": \"chunk\", \"name\": {} }}",
+						json_string(name)
+					)
+				}
+				MiscChunk::Article(name) => format!(
+					"{{ \"type\": \"article\", \"name\": {} }}",
+					json_string(name)
+				),
+				MiscChunk::B8000Text(name) => format!(
+					"{{ \"type\": \"b8000text\", \"name\": {} }}",
+					json_string(name)
+				),
+				MiscChunk::Terminator(name) => format!(
+					"{{ \"type\": \"terminator\", \"name\": {} }}",
+					json_string(name)
+				),
+				MiscChunk::Demo(num) => {
+					format!("{{ \"type\": \"demo\", \"number\": {} }}", num)
+				}
+			})
+			.collect();
+		let lumps: Vec<String> = self
+			.lumps
+			.iter()
+			.map(|lump| {
+				format!(
+					"{{ \"name\": {}, \"section\": \"{}\", \"start_chunk\": {}, \"end_chunk\": {} }}",
+					json_string(&lump.name),
+					section_json_name(lump.section),
+					lump.start_chunk,
+					lump.end_chunk
+				)
+			})
+			.collect();
+
+		let fields = [
+			(
+				"extension",
+				match &self.extension {
+					Some(ext) => json_string(ext),
+					None => "null".to_string(),
+				},
+			),
+			("game", json_string(&self.game.to_string())),
+			("header_chunks", self.header_chunk_count.to_string()),
+			("fonts", json_name_array(&self.fonts)),
+			("fonts_masked", json_name_array(&self.fonts_masked)),
+			("bitmaps", json_name_array(&self.bitmaps)),
+			("bitmaps_masked", json_name_array(&self.bitmaps_masked)),
+			("sprites", json_name_array(&self.sprites)),
+			("tile8_count", self.tile8_count.to_string()),
+			("tile8_names", json_name_array(&self.tile8_names)),
+			("tile8_masked_count", self.tile8_masked_count.to_string()),
+			("tile16_count", self.tile16_count.to_string()),
+			("tile16_names", optional_names(&self.tile16_names)),
+			("tile16_masked_count", self.tile16_masked_count.to_string()),
+			("tile32_count", self.tile32_count.to_string()),
+			("tile32_names", optional_names(&self.tile32_names)),
+			("tile32_masked_count", self.tile32_masked_count.to_string()),
+			("misc_chunks", json_array(&misc_chunks)),
+			("lumps", json_array(&lumps)),
+		];
+		writeln!(f, "{{")?;
+		let mut field_iterator = fields.iter().peekable();
+		while let Some((key, value)) = field_iterator.next() {
+			write!(f, "\t\"{}\": {}", key, value)?;
+			if field_iterator.peek().is_some() {
+				write!(f, ",")?;
+			}
+			writeln!(f)?;
+		}
+		writeln!(f, "}}")
+	}
+
+	pub fn save_json(&self, filename: &str, create_dirs: bool) -> std::io::Result<()> {
+		let json_file = create_output_file(filename, create_dirs)?;
+		let mut json_writer = std::io::BufWriter::new(json_file);
+		self.write_json(&mut json_writer)
+	}
+}
+
+/* A parsed JSON value, and the line it started on for error messages. */
+struct JsonValue {
+	line: usize,
+	kind: JsonKind,
+}
+
+enum JsonKind {
+	Null,
+	Bool(bool),
+	/* Numbers are kept as written, and only converted where they're used. */
+	Number(String),
+	String(String),
+	Array(Vec<JsonValue>),
+	/* Keys are kept in order, so that duplicates can be spotted. */
+	Object(Vec<(String, JsonValue)>),
+}
+
+struct JsonParser<'a> {
+	chars: std::iter::Peekable<std::str::Chars<'a>>,
+	filename: Option<String>,
+	line: usize,
+}
+
+impl<'a> JsonParser<'a> {
+	fn new(data: &'a str, filename: Option<String>) -> JsonParser<'a> {
+		JsonParser {
+			chars: data.chars().peekable(),
+			filename,
+			line: 1,
+		}
+	}
+
+	fn error_at(&self, line: usize, message: String) -> Box<ParseError> {
+		Box::new(ParseError {
+			location: match &self.filename {
+				Some(filename) => format!("{}:{}", filename, line),
+				None => format!("line {}", line),
+			},
+			line,
+			expected: None,
+			found: None,
+			message,
+			line_content: None,
+		})
+	}
+
+	fn error(&self, message: String) -> Box<ParseError> {
+		self.error_at(self.line, message)
+	}
+
+	fn next_char(&mut self) -> Option<char> {
+		let c = self.chars.next();
+		if c == Some('\n') {
+			self.line += 1;
+		}
+		c
+	}
+
+	fn skip_whitespace(&mut self) {
+		while let Some(&c) = self.chars.peek() {
+			if !c.is_whitespace() {
+				break;
+			}
+			self.next_char();
+		}
+	}
+
+	fn expect_char(&mut self, expected: char) -> ParseResult<()> {
+		self.skip_whitespace();
+		match self.next_char() {
+			Some(c) if c == expected => Ok(()),
+			Some(c) => Err(
+				self.error(format!("Expected '{}', but got '{}'!", expected, c))
+			),
+			None => Err(self.error(format!("Expected '{}', but got EOF!", expected))),
+		}
+	}
+
+	fn parse_document(&mut self) -> ParseResult<JsonValue> {
+		let value = self.parse_value()?;
+		self.skip_whitespace();
+		match self.next_char() {
+			None => Ok(value),
+			Some(c) => Err(self
+				.error(format!("Unexpected '{}' after the end of the JSON!", c))),
+		}
+	}
+
+	fn parse_value(&mut self) -> ParseResult<JsonValue> {
+		self.skip_whitespace();
+		let line = self.line;
+		let kind = match self.chars.peek() {
+			Some('{') => self.parse_object()?,
+			Some('[') => self.parse_array()?,
+			Some('"') => JsonKind::String(self.parse_string()?),
+			Some(&c) if c == '-' || c.is_ascii_digit() => self.parse_number(),
+			Some(&c) if c.is_ascii_alphabetic() => {
+				let mut word = String::new();
+				while let Some(&c) = self.chars.peek() {
+					if !c.is_ascii_alphabetic() {
+						break;
+					}
+					word.push(c);
+					self.next_char();
+				}
+				match word.as_str() {
+					"null" => JsonKind::Null,
+					"true" => JsonKind::Bool(true),
+					"false" => JsonKind::Bool(false),
+					_ => {
+						return Err(self
+							.error(format!("Unknown value {}!", word)))
+					}
+				}
+			}
+			Some(&c) => return Err(self.error(format!("Unexpected '{}'!", c))),
+			None => {
+				return Err(self.error("Expected a value, but got EOF!".to_string()))
+			}
+		};
+		Ok(JsonValue { line, kind })
+	}
+
+	fn parse_object(&mut self) -> ParseResult<JsonKind> {
+		self.expect_char('{')?;
+		let mut members = Vec::new();
+		self.skip_whitespace();
+		if self.chars.peek() == Some(&'}') {
+			self.next_char();
+			return Ok(JsonKind::Object(members));
+		}
+		loop {
+			self.skip_whitespace();
+			if self.chars.peek() != Some(&'"') {
+				return Err(self.error("Expected a key string!".to_string()));
+			}
+			let key = self.parse_string()?;
+			self.expect_char(':')?;
+			members.push((key, self.parse_value()?));
+			self.skip_whitespace();
+			match self.next_char() {
+				Some(',') => {}
+				Some('}') => return Ok(JsonKind::Object(members)),
+				_ => {
+					return Err(self
+						.error("Expected ',' or '}' in object!"
+							.to_string()))
+				}
+			}
+		}
+	}
+
+	fn parse_array(&mut self) -> ParseResult<JsonKind> {
+		self.expect_char('[')?;
+		let mut elements = Vec::new();
+		self.skip_whitespace();
+		if self.chars.peek() == Some(&']') {
+			self.next_char();
+			return Ok(JsonKind::Array(elements));
+		}
+		loop {
+			elements.push(self.parse_value()?);
+			self.skip_whitespace();
+			match self.next_char() {
+				Some(',') => {}
+				Some(']') => return Ok(JsonKind::Array(elements)),
+				_ => {
+					return Err(self
+						.error("Expected ',' or ']' in array!".to_string()))
+				}
+			}
+		}
+	}
+
+	fn parse_hex4(&mut self) -> ParseResult<u32> {
+		let mut digits = String::new();
+		for _ in 0..4 {
+			match self.next_char() {
+				Some(c) if c.is_ascii_hexdigit() => digits.push(c),
+				_ => {
+					return Err(self
+						.error("Invalid \\u escape in string!".to_string()))
+				}
+			}
+		}
+		Ok(u32::from_str_radix(&digits, 16).unwrap())
+	}
+
+	fn parse_string(&mut self) -> ParseResult<String> {
+		self.expect_char('"')?;
+		let mut string = String::new();
+		loop {
+			match self.next_char() {
+				Some('"') => return Ok(string),
+				Some('\\') => {
+					let escaped =
+						match self.next_char() {
+							Some('"') => '"',
+							Some('\\') => '\\',
+							Some('/') => '/',
+							Some('b') => '\u{8}',
+							Some('f') => '\u{c}',
+							Some('n') => '\n',
+							Some('r') => '\r',
+							Some('t') => '\t',
+							Some('u') => {
+								let mut code = self.parse_hex4()?;
+								/* Characters outside the BMP are written as surrogate pairs. */
+								if (0xD800..0xDC00).contains(&code)
+								{
+									let low = match (self.next_char(), self.next_char()) {
+									(Some('\\'), Some('u')) => self.parse_hex4()?,
+									_ => 0,
+								};
+									if !(0xDC00..0xE000)
+										.contains(&low)
+									{
+										return Err(self.error(
+										"Invalid \\u escape in string!".to_string(),
+									));
+									}
+									code = 0x10000
+										+ ((code - 0xD800)
+											<< 10) + (low
+										- 0xDC00);
+								}
+								std::char::from_u32(code)
+									.ok_or_else(|| {
+										self.error("Invalid \\u escape in string!".to_string())
+									})?
+							}
+							Some(c) => {
+								return Err(self.error(format!(
+								"Unknown escape sequence \\{} in string!",
+								c
+							)))
+							}
+							None => {
+								return Err(self.error(
+									"Unterminated string!"
+										.to_string(),
+								))
+							}
+						};
+					string.push(escaped);
+				}
+				Some(c) if (c as u32) < 0x20 => {
+					return Err(self
+						.error("Unescaped control character in string!"
+							.to_string()))
+				}
+				Some(c) => string.push(c),
+				None => return Err(self.error("Unterminated string!".to_string())),
+			}
+		}
+	}
+
+	fn parse_number(&mut self) -> JsonKind {
+		let mut number = String::new();
+		while let Some(&c) = self.chars.peek() {
+			if !(c.is_ascii_digit() || "+-.eE".contains(c)) {
+				break;
+			}
+			number.push(c);
+			self.next_char();
+		}
+		JsonKind::Number(number)
+	}
+
+	/* The conversions below report errors at the value which was wrong. */
+
+	fn to_count(&self, key: &str, value: &JsonValue) -> ParseResult<u32> {
+		match &value.kind {
+			JsonKind::Number(number) => {
+				number.parse().map_err(|_| {
+					self.error_at(
+					value.line,
+					format!("\"{}\" should be a non-negative integer, not {}!", key, number),
+				)
+				})
+			}
+			_ => Err(self
+				.error_at(value.line, format!("\"{}\" should be a number!", key))),
+		}
+	}
+
+	fn to_string(&self, key: &str, value: &JsonValue) -> ParseResult<String> {
+		match &value.kind {
+			JsonKind::String(string) => Ok(string.clone()),
+			_ => Err(self
+				.error_at(value.line, format!("\"{}\" should be a string!", key))),
+		}
+	}
+
+	fn to_array<'v>(&self, key: &str, value: &'v JsonValue) -> ParseResult<&'v [JsonValue]> {
+		match &value.kind {
+			JsonKind::Array(elements) => Ok(elements),
+			_ => Err(self
+				.error_at(value.line, format!("\"{}\" should be an array!", key))),
+		}
+	}
+
+	fn to_names(&self, key: &str, value: &JsonValue) -> ParseResult<Vec<String>> {
+		self.to_array(key, value)?
+			.iter()
+			.map(|element| self.to_string(key, element))
+			.collect()
+	}
+
+	fn to_optional_names(
+		&self,
+		key: &str,
+		value: &JsonValue,
+	) -> ParseResult<Option<Vec<String>>> {
+		match value.kind {
+			JsonKind::Null => Ok(None),
+			_ => Ok(Some(self.to_names(key, value)?)),
+		}
+	}
+
+	/* Checks an object's members are all known, and returns a lookup for them. */
+	fn to_object<'v>(
+		&self,
+		key: &str,
+		value: &'v JsonValue,
+		known_keys: &[&str],
+	) -> ParseResult<HashMap<&'v str, &'v JsonValue>> {
+		let members = match &value.kind {
+			JsonKind::Object(members) => members,
+			_ => {
+				return Err(self.error_at(
+					value.line,
+					format!("\"{}\" should be an object!", key),
+				))
+			}
+		};
+		let mut object = HashMap::new();
+		for (member_key, member) in members {
+			if !known_keys.contains(&member_key.as_str()) {
+				return Err(self.error_at(
+					member.line,
+					format!("Unknown key \"{}\" in {}!", member_key, key),
+				));
+			}
+			if object.insert(member_key.as_str(), member).is_some() {
+				return Err(self.error_at(
+					member.line,
+					format!("Duplicate key \"{}\" in {}!", member_key, key),
+				));
+			}
+		}
+		Ok(object)
+	}
+
+	fn member<'v>(
+		&self,
+		object: &HashMap<&str, &'v JsonValue>,
+		object_line: usize,
+		key: &str,
+	) -> ParseResult<&'v JsonValue> {
+		object.get(key)
+			.cloned()
+			.ok_or_else(|| self.error_at(object_line, format!("Missing \"{}\"!", key)))
+	}
+
+	fn to_misc_chunk(&self, value: &JsonValue) -> ParseResult<MiscChunk> {
+		let chunk = self.to_object("misc chunk", value, &["type", "name", "number"])?;
+		let chunk_type =
+			self.to_string("type", self.member(&chunk, value.line, "type")?)?;
+		let name = || self.to_string("name", self.member(&chunk, value.line, "name")?);
+		Ok(match chunk_type.as_str() {
+			"chunk" => MiscChunk::Chunk(name()?),
+			"article" => MiscChunk::Article(name()?),
+			"b8000text" => MiscChunk::B8000Text(name()?),
+			"terminator" => MiscChunk::Terminator(name()?),
+			"demo" => {
+				MiscChunk::Demo(self.to_count(
+					"number",
+					self.member(&chunk, value.line, "number")?,
+				)?)
+			}
+			_ => {
+				return Err(self.error_at(
+					value.line,
+					format!("Unknown misc chunk type {}!", chunk_type),
+				))
+			}
+		})
+	}
+
+	fn to_lump(&self, value: &JsonValue) -> ParseResult<Lump> {
+		let lump = self.to_object(
+			"lump",
+			value,
+			&["name", "section", "start_chunk", "end_chunk"],
+		)?;
+		let section_name =
+			self.to_string("section", self.member(&lump, value.line, "section")?)?;
+		Ok(Lump {
+			name: self.to_string("name", self.member(&lump, value.line, "name")?)?,
+			start_chunk: self.to_count(
+				"start_chunk",
+				self.member(&lump, value.line, "start_chunk")?,
+			)?,
+			end_chunk: self.to_count(
+				"end_chunk",
+				self.member(&lump, value.line, "end_chunk")?,
+			)?,
+			defined_at_line: value.line,
+			section: section_from_json_name(&section_name).ok_or_else(|| {
+				self.error_at(
+					value.line,
+					format!("Unknown section {}!", section_name),
+				)
+			})?,
+		})
+	}
+
+	/* As for scripts, names can only be used once in each section. */
+	fn check_duplicate_names<'v>(
+		&self,
+		section: ChunkSection,
+		names: impl Iterator<Item = (Option<&'v str>, &'v JsonValue)>,
+	) -> ParseResult<()> {
+		let mut seen_names = HashMap::new();
+		for (name, value) in names {
+			if let Some(name) = name {
+				if let Some(first_line) = seen_names.insert(name, value.line) {
+					return Err(self.error_at(
+						value.line,
+						duplicate_name_message(name, section, first_line),
+					));
+				}
+			}
+		}
+		Ok(())
+	}
+
+	fn to_section_names(
+		&self,
+		key: &str,
+		value: &JsonValue,
+		section: ChunkSection,
+	) -> ParseResult<Vec<String>> {
+		let names = self.to_names(key, value)?;
+		let elements = self.to_array(key, value)?;
+		self.check_duplicate_names(
+			section,
+			names.iter().map(|name| Some(name.as_str())).zip(elements),
+		)?;
+		Ok(names)
+	}
+
+	/*
+	 * Builds GfxHeaders from the object write_json() writes. As in scripts,
+	 * everything is optional, and the Game fills in anything left out.
+	 */
+	fn to_headers(&self, value: &JsonValue) -> ParseResult<GfxHeaders> {
+		let object = self.to_object("the script", value, JSON_KEYS)?;
+		let mut headers = GfxHeaders::default();
+		if let Some(game) = object.get("game") {
+			let game_name = self.to_string("game", game)?;
+			headers.game = GameVariant::from_name(&game_name).ok_or_else(|| {
+				self.error_at(game.line, format!("Unknown game {}!", game_name))
+			})?;
+		}
+		headers.extension = match object.get("extension") {
+			Some(JsonValue {
+				kind: JsonKind::Null,
+				..
+			}) => None,
+			Some(extension) => Some(self.to_string("extension", extension)?),
+			None => headers.game.default_extension().map(|e| e.to_string()),
+		};
+		headers.header_chunk_count = match object.get("header_chunks") {
+			Some(count) => self.to_count("header_chunks", count)?,
+			None => headers.game.default_header_chunk_count().unwrap_or(3),
+		};
+		for (key, member) in JSON_KEYS
+			.iter()
+			.filter_map(|key| Some((key, *object.get(key)?)))
+		{
+			match *key {
+				"fonts" => {
+					headers.fonts = self.to_section_names(
+						key,
+						member,
+						ChunkSection::Fonts,
+					)?
+				}
+				"fonts_masked" => {
+					headers.fonts_masked = self.to_section_names(
+						key,
+						member,
+						ChunkSection::FontsMasked,
+					)?
+				}
+				"bitmaps" => {
+					headers.bitmaps = self.to_section_names(
+						key,
+						member,
+						ChunkSection::Bitmaps,
+					)?
+				}
+				"bitmaps_masked" => {
+					headers.bitmaps_masked = self.to_section_names(
+						key,
+						member,
+						ChunkSection::BitmapsMasked,
+					)?
+				}
+				"sprites" => {
+					headers.sprites = self.to_section_names(
+						key,
+						member,
+						ChunkSection::Sprites,
+					)?
+				}
+				"tile8_count" => {
+					headers.tile8_count = self.to_count(key, member)?
+				}
+				"tile8_names" => {
+					headers.tile8_names = self.to_names(key, member)?
+				}
+				"tile8_masked_count" => {
+					headers.tile8_masked_count = self.to_count(key, member)?
+				}
+				"tile16_count" => {
+					headers.tile16_count = self.to_count(key, member)?
+				}
+				"tile16_names" => {
+					headers.tile16_names =
+						self.to_optional_names(key, member)?
+				}
+				"tile16_masked_count" => {
+					headers.tile16_masked_count = self.to_count(key, member)?
+				}
+				"tile32_count" => {
+					headers.tile32_count = self.to_count(key, member)?
+				}
+				"tile32_names" => {
+					headers.tile32_names =
+						self.to_optional_names(key, member)?
+				}
+				"tile32_masked_count" => {
+					headers.tile32_masked_count = self.to_count(key, member)?
+				}
+				"misc_chunks" => {
+					let chunks = self.to_array(key, member)?;
+					headers.misc_chunks = chunks
+						.iter()
+						.map(|chunk| self.to_misc_chunk(chunk))
+						.collect::<ParseResult<_>>()?;
+					self.check_duplicate_names(
+						ChunkSection::Misc,
+						headers.misc_chunks
+							.iter()
+							.map(|chunk| chunk.name())
+							.zip(chunks),
+					)?;
+				}
+				"lumps" => {
+					headers.lumps = self
+						.to_array(key, member)?
+						.iter()
+						.map(|lump| self.to_lump(lump))
+						.collect::<ParseResult<_>>()?
+				}
+				_ => {}
+			}
+		}
+		/* Only now are the sections known, whatever order the keys came in. */
+		for lump in &headers.lumps {
+			self.check_lump_range(&headers, lump)?;
+		}
+		Ok(headers)
+	}
+
+	/*
+	 * Scripts work out lump ranges from where the Lump block is, so JSON ones
+	 * must be somewhere a script could have put them: within their section,
+	 * and either covering some chunks, or empty (ending just before they start).
+	 */
+	fn check_lump_range(&self, headers: &GfxHeaders, lump: &Lump) -> ParseResult<()> {
+		let (section_start, section_end) = headers.section_range(lump.section);
+		let start_chunk = u64::from(lump.start_chunk);
+		let end_chunk = u64::from(lump.end_chunk);
+		if start_chunk < u64::from(section_start)
+			|| start_chunk > u64::from(section_end)
+			|| end_chunk + 1 < start_chunk
+			|| end_chunk >= u64::from(section_end)
+		{
+			return Err(self.error_at(
+				lump.defined_at_line,
+				format!(
+					"Lump \"{}\" runs from chunk {} to {}, which isn't within the {}!",
+					lump.name, lump.start_chunk, lump.end_chunk, lump.section
+				),
+			));
+		}
+		Ok(())
+	}
+}
+
+/* Every key write_json() writes at the top level. */
+const JSON_KEYS: &[&str] = &[
+	"extension",
+	"game",
+	"header_chunks",
+	"fonts",
+	"fonts_masked",
+	"bitmaps",
+	"bitmaps_masked",
+	"sprites",
+	"tile8_count",
+	"tile8_names",
+	"tile8_masked_count",
+	"tile16_count",
+	"tile16_names",
+	"tile16_masked_count",
+	"tile32_count",
+	"tile32_names",
+	"tile32_masked_count",
+	"misc_chunks",
+	"lumps",
+];
+
+fn section_from_json_name(name: &str) -> Option<ChunkSection> {
+	[
+		ChunkSection::Fonts,
+		ChunkSection::FontsMasked,
+		ChunkSection::Bitmaps,
+		ChunkSection::BitmapsMasked,
+		ChunkSection::Sprites,
+		ChunkSection::Misc,
+	]
+	.iter()
+	.cloned()
+	.find(|&section| section_json_name(section) == name)
+}
+
+/* Parses GfxHeaders from JSON in the format write_json() writes. */
+pub fn parse_gfx_json_str(json_data: &str) -> ParseResult<GfxHeaders> {
+	parse_json_headers(json_data, None)
+}
+
+fn parse_json_headers(json_data: &str, filename: Option<String>) -> ParseResult<GfxHeaders> {
+	let mut parser = JsonParser::new(json_data, filename);
+	let document = parser.parse_document()?;
+	parser.to_headers(&document)
+}
+
+pub fn parse_gfx_json(filename: impl AsRef<Path>) -> ParseResult<GfxHeaders> {
+	let filename = filename.as_ref();
+	let location = filename.display().to_string();
+	let json_data = std::fs::read_to_string(filename).map_err(|e| {
+		Box::new(ParseError {
+			location: location.clone(),
+			line: 0,
+			expected: None,
+			found: None,
+			message: e.to_string(),
+			line_content: None,
+		})
+	})?;
+	parse_json_headers(&json_data, Some(location))
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parse_gfx_script;
+	use parse_gfx_script_str;
+
+	#[test]
+	fn json_small_script() {
+		let headers = parse_gfx_script_str(
+			"Extension \"CK4\"\n\
+			 HeaderChunks 3\n\
+			 Bitmaps {\n\tLump \"TITLES\" {\n\t\t\"TITLE\"\n\t\t\"SAY\\\"HI\"\n\t}\n}\n\
+			 Sprites {\n\t\"KEEN\"\n}\n\
+			 Tiles16 4\n\
+			 Externs {\n\tChunk \"README\"\n\tDemo 0\n}\n",
+		)
+		.unwrap();
+		let mut out = Vec::new();
+		headers.write_json(&mut out).unwrap();
+		assert_eq!(
+			String::from_utf8(out).unwrap(),
+			"{\n\
+			 \t\"extension\": \"CK4\",\n\
+			 \t\"game\": \"Custom\",\n\
+			 \t\"header_chunks\": 3,\n\
+			 \t\"fonts\": [],\n\
+			 \t\"fonts_masked\": [],\n\
+			 \t\"bitmaps\": [\n\t\t\"TITLE\",\n\t\t\"SAY\\\"HI\"\n\t],\n\
+			 \t\"bitmaps_masked\": [],\n\
+			 \t\"sprites\": [\n\t\t\"KEEN\"\n\t],\n\
+			 \t\"tile8_count\": 0,\n\
+			 \t\"tile8_names\": [],\n\
+			 \t\"tile8_masked_count\": 0,\n\
+			 \t\"tile16_count\": 4,\n\
+			 \t\"tile16_names\": null,\n\
+			 \t\"tile16_masked_count\": 0,\n\
+			 \t\"tile32_count\": 0,\n\
+			 \t\"tile32_names\": null,\n\
+			 \t\"tile32_masked_count\": 0,\n\
+			 \t\"misc_chunks\": [\n\
+			 \t\t{ \"type\": \"chunk\", \"name\": \"README\" },\n\
+			 \t\t{ \"type\": \"demo\", \"number\": 0 }\n\
+			 \t],\n\
+			 \t\"lumps\": [\n\
+			 \t\t{ \"name\": \"TITLES\", \"section\": \"bitmaps\", \"start_chunk\": 3, \"end_chunk\": 4 }\n\
+			 \t]\n\
+			 }\n"
+		);
+	}
+
+	#[test]
+	fn json_matches_script() {
+		let script = parse_gfx_script_str(
+			"Game Keen4\n\
+			 Fonts {\n\t\"MAIN\"\n}\n\
+			 Bitmaps {\n\tLump \"TITLES\" {\n\t\t\"TITLE\"\n\t\t\"CREDITS\"\n\t}\n}\n\
+			 Sprites {\n\tLump \"KEEN\" {\n\t\t\"KEENSTAND\"\n\t}\n}\n\
+			 Tiles8 {\n\t\"BLANK\"\n}\n\
+			 Tiles16 {\n\t\"SKY\"\n\t\"\"\n}\n\
+			 Externs {\n\tArticle \"STORY\"\n\tDemo 0\n}\n",
+		)
+		.unwrap();
+		let json = parse_gfx_json_str(
+			r#"{
+				"game": "Keen4",
+				"fonts": ["MAIN"],
+				"bitmaps": ["TITLE", "CREDITS"],
+				"sprites": ["KEENSTAND"],
+				"tile8_count": 1,
+				"tile8_names": ["BLANK"],
+				"tile16_count": 2,
+				"tile16_names": ["SKY", ""],
+				"misc_chunks": [
+					{ "type": "article", "name": "STORY" },
+					{ "number": 0, "type": "demo" }
+				],
+				"lumps": [
+					{ "name": "TITLES", "section": "bitmaps", "start_chunk": 4, "end_chunk": 5 },
+					{ "name": "KEEN", "section": "sprites", "start_chunk": 6, "end_chunk": 6 }
+				]
+			}"#,
+		)
+		.unwrap();
+		assert_eq!(json, script);
+		assert_eq!(json.extension.as_deref(), Some("CK4"));
+	}
+
+	#[test]
+	fn json_file_round_trip() {
+		let headers = parse_gfx_script("scripts/keen4.idgrab").unwrap();
+		let json_path = std::env::temp_dir()
+			.join(format!("idgrab-round-trip-{}.json", std::process::id()));
+		headers.save_json(json_path.to_str().unwrap(), false)
+			.unwrap();
+		let read_back = parse_gfx_json(&json_path);
+		std::fs::remove_file(&json_path).unwrap();
+		assert_eq!(read_back.unwrap(), headers);
+	}
+
+	#[test]
+	fn json_errors() {
+		let error_message = |json: &str| parse_gfx_json_str(json).unwrap_err().to_string();
+		assert_eq!(
+			error_message("{\n\t\"bitmaps\": [\"A\", 3]\n}"),
+			"line 2: \"bitmaps\" should be a string!"
+		);
+		assert_eq!(
+			error_message("{\n\t\"sprite\": []\n}"),
+			"line 2: Unknown key \"sprite\" in the script!"
+		);
+		assert_eq!(
+			error_message("{ \"tile16_count\": -1 }"),
+			"line 1: \"tile16_count\" should be a non-negative integer, not -1!"
+		);
+		assert_eq!(
+			error_message(
+				"{\n\t\"misc_chunks\": [\n\t\t{ \"type\": \"music\" }\n\t]\n}"
+			),
+			"line 3: Unknown misc chunk type music!"
+		);
+		assert_eq!(
+			error_message("{ \"fonts\": [\"A\" }"),
+			"line 1: Expected ',' or ']' in array!"
+		);
+		assert_eq!(
+			error_message("{ \"extension\": \"CK4"),
+			"line 1: Unterminated string!"
+		);
+		assert_eq!(
+			error_message("{} {}"),
+			"line 1: Unexpected '{' after the end of the JSON!"
+		);
+		assert_eq!(
+			error_message("{\n\t\"sprites\": [\n\t\t\"KEEN\",\n\t\t\"KEEN\"\n\t]\n}"),
+			"line 4: Duplicate name \"KEEN\" in the sprites (first used on line 3)!"
+		);
+		assert_eq!(
+			error_message(
+				"{ \"misc_chunks\": [\n\t{ \"type\": \"chunk\", \"name\": \"A\" },\n\t{ \"type\": \"article\", \"name\": \"A\" }\n] }"
+			),
+			"line 3: Duplicate name \"A\" in the misc chunks (first used on line 2)!"
+		);
+
+		/* Lumps must be within their section, and can't end before they start. */
+		let lump_json = |lump: &str| {
+			format!(
+				"{{\n\t\"bitmaps\": [\"A\", \"B\"],\n\t\"sprites\": [\"S\"],\n\t\"lumps\": [\n\t\t{}\n\t]\n}}",
+				lump
+			)
+		};
+		for (start_chunk, end_chunk) in [(4, 2), (3, 5), (2, 3), (5, 5), (6, 6)].iter() {
+			let lump = format!(
+				"{{ \"name\": \"L\", \"section\": \"bitmaps\", \"start_chunk\": {}, \"end_chunk\": {} }}",
+				start_chunk, end_chunk
+			);
+			assert_eq!(
+				error_message(&lump_json(&lump)),
+				format!(
+					"line 5: Lump \"L\" runs from chunk {} to {}, which isn't within the bitmaps!",
+					start_chunk, end_chunk
+				)
+			);
+		}
+		/* Empty lumps end just before they start, as in scripts. */
+		for lump in [
+			"{ \"name\": \"L\", \"section\": \"bitmaps\", \"start_chunk\": 3, \"end_chunk\": 4 }",
+			"{ \"name\": \"L\", \"section\": \"bitmaps\", \"start_chunk\": 5, \"end_chunk\": 4 }",
+			"{ \"name\": \"L\", \"section\": \"sprites\", \"start_chunk\": 5, \"end_chunk\": 5 }",
+		]
+		.iter()
+		{
+			assert!(parse_gfx_json_str(&lump_json(lump)).is_ok(), "{}", lump);
+		}
+	}
+
+	#[test]
+	fn json_string_escapes() {
+		let headers =
+			parse_gfx_json_str(r#"{ "bitmaps": ["A\"B\\C\/D\u0041\uD83D\uDE00"] }"#)
+				.unwrap();
+		assert_eq!(headers.bitmaps, vec!["A\"B\\C/DA\u{1F600}"]);
+		assert!(parse_gfx_json_str(r#"{ "bitmaps": ["\ud83d"] }"#).is_err());
+	}
+}
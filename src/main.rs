@@ -20,22 +20,72 @@
  */
 #![allow(dead_code)]
 
-pub use std::{convert::TryInto, fs::File, io::Write, path::Path};
+pub use std::{collections::HashMap, convert::TryInto, fs::File, io::Write, path::Path};
 
 mod binary_io;
 use binary_io::*;
+mod cli;
+mod diff;
+use cli::show_usage;
 mod igrab;
+mod json;
+mod modid;
+mod omnispeak;
 mod parser;
+use parser::{ParseError, ParseResult};
+mod validate;
 use igrab::*;
+use modid::*;
+use omnispeak::*;
+
+/* The version from Cargo.toml. Building with the Makefile doesn't set it. */
+const IDGRAB_VERSION: Option<&str> = option_env!("CARGO_PKG_VERSION");
 
 #[derive(Debug)]
 struct Lump {
 	name: String,
 	start_chunk: u32,
 	end_chunk: u32,
+	/* The line of the script the lump was defined on, for diagnostics. */
+	defined_at_line: usize,
+	/* The section the lump was defined in, which all its chunks should be in. */
+	section: ChunkSection,
 }
 
-#[derive(Debug)]
+/* Lumps are the same if they cover the same chunks, wherever they were defined. */
+impl PartialEq for Lump {
+	fn eq(&self, other: &Lump) -> bool {
+		self.name == other.name
+			&& self.start_chunk == other.start_chunk
+			&& self.end_chunk == other.end_chunk
+	}
+}
+
+/* The parts of the script which chunks (and so lumps) can be defined in. */
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
+enum ChunkSection {
+	Fonts,
+	FontsMasked,
+	Bitmaps,
+	BitmapsMasked,
+	Sprites,
+	Misc,
+}
+
+impl std::fmt::Display for ChunkSection {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ChunkSection::Fonts => write!(f, "fonts"),
+			ChunkSection::FontsMasked => write!(f, "masked fonts"),
+			ChunkSection::Bitmaps => write!(f, "bitmaps"),
+			ChunkSection::BitmapsMasked => write!(f, "masked bitmaps"),
+			ChunkSection::Sprites => write!(f, "sprites"),
+			ChunkSection::Misc => write!(f, "misc chunks"),
+		}
+	}
+}
+
+#[derive(PartialEq, Debug)]
 enum MiscChunk {
 	Chunk(String),
 	Article(String),
@@ -44,9 +94,86 @@ enum MiscChunk {
 	Demo(u32),
 }
 
-#[derive(Default, Debug)]
+impl MiscChunk {
+	/* The chunk's name, or None for demos, which are only numbered. */
+	fn name(&self) -> Option<&str> {
+		match self {
+			MiscChunk::Chunk(name)
+			| MiscChunk::Article(name)
+			| MiscChunk::B8000Text(name)
+			| MiscChunk::Terminator(name) => Some(name),
+			MiscChunk::Demo(_) => None,
+		}
+	}
+}
+
+/*
+ * The game a script is for, set with the Game keyword. Each game provides
+ * defaults which explicit keywords (Extension and HeaderChunks) override,
+ * though validation warns if they no longer match what the game expects:
+ *   Keen4: Extension "CK4", 3 header chunks
+ *   Keen5: Extension "CK5", 3 header chunks
+ *   Keen6: Extension "CK6", 3 header chunks
+ *   Custom: no defaults
+ */
+#[derive(PartialEq, Clone, Copy, Default, Debug)]
+enum GameVariant {
+	Keen4,
+	Keen5,
+	Keen6,
+	#[default]
+	Custom,
+}
+
+impl GameVariant {
+	fn from_name(name: &str) -> Option<GameVariant> {
+		match name {
+			"Keen4" => Some(GameVariant::Keen4),
+			"Keen5" => Some(GameVariant::Keen5),
+			"Keen6" => Some(GameVariant::Keen6),
+			"Custom" => Some(GameVariant::Custom),
+			_ => None,
+		}
+	}
+
+	fn default_extension(&self) -> Option<&'static str> {
+		match self {
+			GameVariant::Keen4 => Some("CK4"),
+			GameVariant::Keen5 => Some("CK5"),
+			GameVariant::Keen6 => Some("CK6"),
+			GameVariant::Custom => None,
+		}
+	}
+
+	fn default_header_chunk_count(&self) -> Option<u32> {
+		match self {
+			GameVariant::Keen4 | GameVariant::Keen5 | GameVariant::Keen6 => Some(3),
+			GameVariant::Custom => None,
+		}
+	}
+}
+
+impl std::fmt::Display for GameVariant {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			GameVariant::Keen4 => write!(f, "Keen4"),
+			GameVariant::Keen5 => write!(f, "Keen5"),
+			GameVariant::Keen6 => write!(f, "Keen6"),
+			GameVariant::Custom => write!(f, "Custom"),
+		}
+	}
+}
+
+/* The names of the header chunks, which hold the picture and sprite tables. */
+const STRUCT_CHUNK_NAMES: &[&str] = &["STRUCTPIC", "STRUCTPICM", "STRUCTSPRITE"];
+
+/* GFXINFOE has a field for each of the three header chunks, so needs exactly three. */
+const GFXINFOE_HEADER_CHUNK_COUNT: u32 = 3;
+
+#[derive(Default, PartialEq, Debug)]
 struct GfxHeaders {
 	extension: Option<String>,
+	game: GameVariant,
 	header_chunk_count: u32,
 	fonts: Vec<String>,
 	fonts_masked: Vec<String>,
@@ -54,15 +181,75 @@ struct GfxHeaders {
 	bitmaps_masked: Vec<String>,
 	sprites: Vec<String>,
 	tile8_count: u32,
+	/* Names for tiles within the tile8 chunk, from Tiles8 { … }. */
+	tile8_names: Vec<String>,
 	tile8_masked_count: u32,
 	tile16_count: u32,
+	/*
+	 * Names for individual tile16s, from Tiles16 { … }, or None if the script
+	 * just gave a count. Empty names are unnamed.
+	 */
+	tile16_names: Option<Vec<String>>,
 	tile16_masked_count: u32,
 	tile32_count: u32,
+	/* Names for individual tile32s, as for tile16_names. */
+	tile32_names: Option<Vec<String>>,
 	tile32_masked_count: u32,
 	misc_chunks: Vec<MiscChunk>,
 	lumps: Vec<Lump>,
 }
 
+/* Which formats GfxHeaders::write_all_formats() writes, and how. */
+struct OutputOptions {
+	gfxinfoe: bool,
+	modid: bool,
+	omnispeak: bool,
+	igrab_header: bool,
+	igrab_asm: bool,
+	/* The directory to write files to. Defaults to the current directory. */
+	output_dir: Option<std::path::PathBuf>,
+	create_dirs: bool,
+	igrab: IGrabOptions,
+	modid_options: ModidOptions,
+	omnispeak_options: OmnispeakOptions,
+}
+
+/* The filenames GfxHeaders::write_formats() uses for each format. */
+struct OutputFilenames {
+	igrab_header: String,
+	igrab_asm: String,
+	omnispeak: String,
+	modid: String,
+	gfxinfoe: String,
+}
+
+impl Default for OutputOptions {
+	fn default() -> OutputOptions {
+		OutputOptions {
+			gfxinfoe: true,
+			modid: true,
+			omnispeak: true,
+			igrab_header: true,
+			igrab_asm: true,
+			output_dir: None,
+			create_dirs: true,
+			igrab: IGrabOptions::default(),
+			modid_options: ModidOptions::default(),
+			omnispeak_options: OmnispeakOptions::default(),
+		}
+	}
+}
+
+/* Creates an output file, first creating any missing parent directories if create_dirs is set. */
+fn create_output_file(filename: impl AsRef<Path>, create_dirs: bool) -> std::io::Result<File> {
+	if create_dirs {
+		if let Some(parent) = filename.as_ref().parent() {
+			std::fs::create_dir_all(parent)?;
+		}
+	}
+	File::create(filename)
+}
+
 impl GfxHeaders {
 	fn num_chunks(&self) -> u32 {
 		self.header_chunk_count
@@ -78,6 +265,80 @@ impl GfxHeaders {
 			+ self.misc_chunks.len() as u32
 	}
 
+	/* The chunk number of the first misc chunk of a given kind, if there are any. */
+	fn first_misc_chunk(&self, is_kind: impl Fn(&MiscChunk) -> bool) -> Option<u32> {
+		(self.misc_start()..)
+			.zip(&self.misc_chunks)
+			.find(|(_, misc)| is_kind(misc))
+			.map(|(chunk_id, _)| chunk_id)
+	}
+
+	fn b8000text_start(&self) -> Option<u32> {
+		self.first_misc_chunk(|misc| matches!(misc, MiscChunk::B8000Text(_)))
+	}
+
+	fn article_start(&self) -> Option<u32> {
+		self.first_misc_chunk(|misc| matches!(misc, MiscChunk::Article(_)))
+	}
+
+	/* The START* labels in the "File offsets for data items" section, and their values. */
+	fn start_defines(&self, igrab_options: &IGrabOptions) -> Vec<(&'static str, u32)> {
+		let mut start_defines = vec![
+			("STARTFONT", self.fonts_start()),
+			("STARTFONTM", self.fonts_masked_start()),
+			("STARTPICS", self.bitmaps_start()),
+			("STARTPICM", self.bitmaps_masked_start()),
+			("STARTSPRITES", self.sprites_start()),
+			("STARTTILE8", self.tile8_start()),
+			("STARTTILE8M", self.tile8_masked_start()),
+			("STARTTILE16", self.tile16_start()),
+			("STARTTILE16M", self.tile16_masked_start()),
+			("STARTTILE32", self.tile32_start()),
+			("STARTTILE32M", self.tile32_masked_start()),
+		];
+		if !self.misc_chunks.is_empty() || !igrab_options.skip_empty_extern_start {
+			start_defines.push(("STARTEXTERNS", self.misc_start()));
+		}
+		if igrab_options.emit_b8000text_start {
+			if let Some(b8000text_start) = self.b8000text_start() {
+				start_defines.push(("STARTB800TEXT", b8000text_start));
+			}
+		}
+		if igrab_options.emit_article_start {
+			if let Some(article_start) = self.article_start() {
+				start_defines.push(("STARTARTICLE", article_start));
+			}
+		}
+		start_defines
+	}
+
+	fn demo_count(&self) -> u32 {
+		self.misc_chunks
+			.iter()
+			.filter(|misc| matches!(misc, MiscChunk::Demo(_)))
+			.count() as u32
+	}
+
+	/* The index within the tile8 chunk and name of each named tile8. */
+	fn named_tile8s(&self) -> impl Iterator<Item = (u32, &String)> {
+		(0..).zip(&self.tile8_names)
+			.filter(|(_, name)| !name.is_empty())
+	}
+
+	/* The chunk number and name of each named tile16. */
+	fn named_tile16s(&self) -> impl Iterator<Item = (u32, &String)> {
+		(self.tile16_start()..)
+			.zip(self.tile16_names.iter().flatten())
+			.filter(|(_, name)| !name.is_empty())
+	}
+
+	/* The chunk number and name of each named tile32. */
+	fn named_tile32s(&self) -> impl Iterator<Item = (u32, &String)> {
+		(self.tile32_start()..)
+			.zip(self.tile32_names.iter().flatten())
+			.filter(|(_, name)| !name.is_empty())
+	}
+
 	fn fonts_start(&self) -> u32 {
 		self.header_chunk_count
 	}
@@ -126,6 +387,51 @@ impl GfxHeaders {
 		self.tile32_masked_start() + self.tile32_masked_count
 	}
 
+	/* Which section a chunk is in. Header and tile chunks aren't in one. */
+	/* The first chunk of a section, and the chunk after its last one. */
+	fn section_range(&self, section: ChunkSection) -> (u32, u32) {
+		match section {
+			ChunkSection::Fonts => (self.fonts_start(), self.fonts_masked_start()),
+			ChunkSection::FontsMasked => {
+				(self.fonts_masked_start(), self.bitmaps_start())
+			}
+			ChunkSection::Bitmaps => {
+				(self.bitmaps_start(), self.bitmaps_masked_start())
+			}
+			ChunkSection::BitmapsMasked => {
+				(self.bitmaps_masked_start(), self.sprites_start())
+			}
+			ChunkSection::Sprites => (
+				self.sprites_start(),
+				self.sprites_start() + self.sprites.len() as u32,
+			),
+			ChunkSection::Misc => (
+				self.misc_start(),
+				self.misc_start() + self.misc_chunks.len() as u32,
+			),
+		}
+	}
+
+	fn chunk_section(&self, chunk: u32) -> Option<ChunkSection> {
+		if chunk < self.fonts_start() {
+			None
+		} else if chunk < self.fonts_masked_start() {
+			Some(ChunkSection::Fonts)
+		} else if chunk < self.bitmaps_start() {
+			Some(ChunkSection::FontsMasked)
+		} else if chunk < self.bitmaps_masked_start() {
+			Some(ChunkSection::Bitmaps)
+		} else if chunk < self.sprites_start() {
+			Some(ChunkSection::BitmapsMasked)
+		} else if chunk < self.tile8_start() {
+			Some(ChunkSection::Sprites)
+		} else if chunk < self.misc_start() {
+			None
+		} else {
+			Some(ChunkSection::Misc)
+		}
+	}
+
 	fn chunk_name(&self, chunk: u32) -> Option<String> {
 		if chunk < self.fonts_start() {
 			None
@@ -150,9 +456,20 @@ impl GfxHeaders {
 		}
 	}
 
-	fn omnispeak_chunk_name(&self, chunk: u32) -> Option<String> {
+	fn omnispeak_chunk_name(
+		&self,
+		chunk: u32,
+		omnispeak_options: &OmnispeakOptions,
+	) -> Option<String> {
 		if chunk < self.fonts_start() {
-			None
+			/* These match the header chunk variables at the top of the CFG. */
+			if self.header_chunk_count == 3 {
+				STRUCT_CHUNK_NAMES
+					.get(chunk as usize)
+					.map(|name| name.to_string())
+			} else {
+				Some(format!("{}{}", omnispeak_options.struct_slot_prefix, chunk))
+			}
 		} else if chunk < self.fonts_masked_start() {
 			Some(format!(
 				"FON_{}",
@@ -170,7 +487,7 @@ impl GfxHeaders {
 			))
 		} else if chunk < self.sprites_start() {
 			Some(format!(
-				"PICM_{}",
+				"MPIC_{}",
 				self.bitmaps_masked[(chunk - self.bitmaps_masked_start()) as usize]
 			))
 		} else if chunk < self.tile8_start() {
@@ -184,6 +501,15 @@ impl GfxHeaders {
 	}
 
 	fn write_gfxinfoe(&self, writer: &mut dyn std::io::Write) -> std::io::Result<()> {
+		if self.header_chunk_count != GFXINFOE_HEADER_CHUNK_COUNT {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidInput,
+				format!(
+					"GFXINFOE needs {} header chunks, but there are {}",
+					GFXINFOE_HEADER_CHUNK_COUNT, self.header_chunk_count
+				),
+			));
+		}
 		// Tile counts
 		write_le16(self.tile8_count as u16, writer)?;
 		write_le16(self.tile8_masked_count as u16, writer)?;
@@ -207,97 +533,287 @@ impl GfxHeaders {
 		write_le16(self.bitmaps_masked_start() as u16, writer)?;
 		write_le16(self.sprites_start() as u16, writer)?;
 		// Header chunks
-		write_le16(0, writer)?;
-		write_le16(1, writer)?;
-		write_le16(2, writer)?;
+		for chunk_id in 0..GFXINFOE_HEADER_CHUNK_COUNT {
+			write_le16(chunk_id as u16, writer)?;
+		}
 		// Miscs
 		write_le16(self.misc_chunks.len() as u16, writer)?;
 		write_le16(self.misc_start() as u16, writer)?;
 		Ok(())
 	}
 
-	fn save_gfxinfoe(&self, filename: &str) -> std::io::Result<()> {
-		let gfxinfoe_file = std::fs::File::create(filename)?;
+	/*
+	 * Reads back a GFXINFOE, as written by write_gfxinfoe(). GFXINFOE only
+	 * has counts, so every chunk gets a placeholder name (e.g. PIC0000), and
+	 * misc chunks are all Chunks. Fonts aren't counted either: everything
+	 * between the 3 header chunks and the bitmaps is taken to be a font.
+	 */
+	fn read_gfxinfoe(reader: &mut dyn std::io::Read) -> std::io::Result<GfxHeaders> {
+		let mut fields = [0u16; 23];
+		for field in fields.iter_mut() {
+			*field = read_le16(reader)?;
+		}
+		let field = |i: usize| u32::from(fields[i]);
+		let placeholders = |prefix: &str, count: u32| -> Vec<String> {
+			(0..count).map(|i| format!("{}{:04}", prefix, i)).collect()
+		};
+		let header_chunk_count = GFXINFOE_HEADER_CHUNK_COUNT;
+		let bitmaps_start = field(15);
+		if bitmaps_start < header_chunk_count {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				"GFXINFOE bitmaps start before the end of the header chunks",
+			));
+		}
+		let headers = GfxHeaders {
+			header_chunk_count,
+			fonts: placeholders("FON", bitmaps_start - header_chunk_count),
+			bitmaps: placeholders("PIC", field(12)),
+			bitmaps_masked: placeholders("MPIC", field(13)),
+			sprites: placeholders("SPR", field(14)),
+			tile8_count: field(0),
+			tile8_masked_count: field(1),
+			tile16_count: field(2),
+			tile16_masked_count: field(3),
+			tile32_count: field(4),
+			tile32_masked_count: field(5),
+			misc_chunks: (0..field(21))
+				.map(|i| MiscChunk::Chunk(format!("EXTERN{:04}", i)))
+				.collect(),
+			..Default::default()
+		};
+		/* Everything else follows from the counts, so must agree with them. */
+		let mut out = Vec::new();
+		headers.write_gfxinfoe(&mut out)?;
+		let expected: Vec<u8> = fields
+			.iter()
+			.flat_map(|f| f.to_le_bytes().to_vec())
+			.collect();
+		if out != expected {
+			return Err(std::io::Error::new(
+				std::io::ErrorKind::InvalidData,
+				"GFXINFOE chunk starts don't match its counts",
+			));
+		}
+		Ok(headers)
+	}
+
+	/*
+	 * Writes every enabled output format, with names derived from base_name:
+	 * GFXE_{base}.H, GFXE_{base}.EQU, GFXCHUNKS_{base}.CFG and MOD_{base}.DEF.
+	 * The GFXINFOE file is always GFXINFO.E, whatever the base name.
+	 */
+	fn write_all_formats(&self, base_name: &str, opts: &OutputOptions) -> std::io::Result<()> {
+		let filenames = OutputFilenames {
+			igrab_header: format!("GFXE_{}.H", base_name),
+			igrab_asm: format!("GFXE_{}.EQU", base_name),
+			omnispeak: format!("GFXCHUNKS_{}.CFG", base_name),
+			modid: format!("MOD_{}.DEF", base_name),
+			gfxinfoe: "GFXINFO.E".to_string(),
+		};
+		self.write_formats(&filenames, opts)
+	}
+
+	/*
+	 * Writes every enabled output format for --all: {prefix}.gfx,
+	 * {prefix}.def, {prefix}_gfx.txt, and GFX{EXT}.H and GFX{EXT}.EQU next
+	 * to them. Scripts without an Extension use "TMP" for EXT.
+	 */
+	fn write_all_with_prefix(&self, prefix: &str, opts: &OutputOptions) -> std::io::Result<()> {
+		let ext = self.extension.as_deref().unwrap_or("TMP");
+		let prefix_dir = Path::new(prefix).parent().unwrap_or_else(|| Path::new(""));
+		let beside_prefix =
+			|filename: String| prefix_dir.join(filename).to_string_lossy().into_owned();
+		let filenames = OutputFilenames {
+			igrab_header: beside_prefix(format!("GFX{}.H", ext)),
+			igrab_asm: beside_prefix(format!("GFX{}.EQU", ext)),
+			omnispeak: format!("{}_gfx.txt", prefix),
+			modid: format!("{}.def", prefix),
+			gfxinfoe: format!("{}.gfx", prefix),
+		};
+		self.write_formats(&filenames, opts)
+	}
+
+	fn write_formats(
+		&self,
+		filenames: &OutputFilenames,
+		opts: &OutputOptions,
+	) -> std::io::Result<()> {
+		let dir = opts.output_dir.clone().unwrap_or_default();
+		let path = |filename: &str| dir.join(filename).to_string_lossy().into_owned();
+		if opts.igrab_header {
+			self.save_igrab_header(
+				&path(&filenames.igrab_header),
+				&opts.igrab,
+				opts.create_dirs,
+			)?;
+		}
+		if opts.igrab_asm {
+			self.save_igrab_asm_header(
+				&path(&filenames.igrab_asm),
+				&opts.igrab,
+				opts.create_dirs,
+			)?;
+		}
+		if opts.omnispeak {
+			self.save_omnispeak_cfg(
+				&path(&filenames.omnispeak),
+				&opts.omnispeak_options,
+				opts.create_dirs,
+			)?;
+		}
+		if opts.modid {
+			self.save_modid_script(
+				&path(&filenames.modid),
+				&opts.modid_options,
+				opts.create_dirs,
+			)?;
+		}
+		if opts.gfxinfoe {
+			self.save_gfxinfoe(&path(&filenames.gfxinfoe), opts.create_dirs)?;
+		}
+		Ok(())
+	}
+
+	fn save_gfxinfoe(&self, filename: &str, create_dirs: bool) -> std::io::Result<()> {
+		let gfxinfoe_file = create_output_file(filename, create_dirs)?;
 		let mut gfxinfoe_writer = std::io::BufWriter::new(gfxinfoe_file);
 		self.write_gfxinfoe(&mut gfxinfoe_writer)
 	}
 
-	fn write_modid_script(&self, script: &mut dyn std::io::Write) -> std::io::Result<()> {
+	fn write_modid_script(
+		&self,
+		script: &mut dyn std::io::Write,
+		modid_options: &ModidOptions,
+	) -> std::io::Result<()> {
 		writeln!(script, "# ModID Script: Automatically Generated")?;
+		if modid_options.emit_version_comment {
+			match IDGRAB_VERSION {
+				Some(version) => {
+					writeln!(script, "# Generated by idGrab {}", version)?
+				}
+				None => writeln!(script, "# Generated by idGrab")?,
+			}
+			if let Some(source) = &modid_options.source_filename {
+				writeln!(script, "# Source: {}", source)?;
+			}
+		}
 		writeln!(script, "GALAXY")?;
 		if let Some(ext) = &self.extension {
 			writeln!(script, "\tGAMEEXT {}", ext)?;
 		}
-		writeln!(script, "\tGRSTARTS 3")?;
+		writeln!(script, "\tGRSTARTS {}", self.header_chunk_count)?;
 		//TODO: CKPATCH options
 		//writeln!(script, "\tEXEINFO ajd.exe 0x3F630 0x259B0 0x36F4E 0x2C00")?;
 		//writeln!(script, "\tCKPATCHVER 1.6")?;
 		writeln!(script, "\tCHUNKS {}", self.num_chunks())?;
 
 		let mut chunk_count = self.header_chunk_count;
-		writeln!(script, "\t\tFONT\t\t{} {}", self.fonts.len(), chunk_count)?;
+		modid_options.write_section(
+			script,
+			"FONT",
+			self.fonts.len() as u32,
+			chunk_count,
+			None,
+		)?;
 		chunk_count += self.fonts.len() as u32;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tFONTM\t\t{} {}",
-			self.fonts_masked.len(),
-			chunk_count
+			"FONTM",
+			self.fonts_masked.len() as u32,
+			chunk_count,
+			None,
 		)?;
 		chunk_count += self.fonts_masked.len() as u32;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tPICS\t\t{} {} 0",
-			self.bitmaps.len(),
-			chunk_count
+			"PICS",
+			self.bitmaps.len() as u32,
+			chunk_count,
+			Some(0),
 		)?;
 		chunk_count += self.bitmaps.len() as u32;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tPICM\t\t{} {} 1",
-			self.bitmaps_masked.len(),
-			chunk_count
+			"PICM",
+			self.bitmaps_masked.len() as u32,
+			chunk_count,
+			Some(1),
 		)?;
 		chunk_count += self.bitmaps_masked.len() as u32;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tSPRITES\t\t{} {} 2",
-			self.sprites.len(),
-			chunk_count
+			"SPRITES",
+			self.sprites.len() as u32,
+			chunk_count,
+			Some(2),
 		)?;
 		chunk_count += self.sprites.len() as u32;
-		writeln!(script, "\t\tTILE8\t\t{} {}", self.tile8_count, chunk_count)?;
+		if self.tile8_count != 0 || modid_options.explicit_zero_tile8 {
+			modid_options.write_section(
+				script,
+				"TILE8",
+				self.tile8_count,
+				chunk_count,
+				None,
+			)?;
+		}
 		chunk_count += if self.tile8_count != 0 { 1 } else { 0 }; /* Tile8s are stored in a single chunk. */
-		writeln!(
-			script,
-			"\t\tTILE8M\t\t{} {}",
-			self.tile8_masked_count, chunk_count
-		)?;
+		if self.tile8_masked_count != 0 || modid_options.explicit_zero_tile8 {
+			modid_options.write_section(
+				script,
+				"TILE8M",
+				self.tile8_masked_count,
+				chunk_count,
+				None,
+			)?;
+		}
 		chunk_count += if self.tile8_masked_count != 0 { 1 } else { 0 }; /* …as are Tile8ms. */
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tTILE16\t\t{} {}",
-			self.tile16_count, chunk_count
+			"TILE16",
+			self.tile16_count,
+			chunk_count,
+			None,
 		)?;
 		chunk_count += self.tile16_count;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tTILE16M\t\t{} {}",
-			self.tile16_masked_count, chunk_count
+			"TILE16M",
+			self.tile16_masked_count,
+			chunk_count,
+			None,
 		)?;
 		chunk_count += self.tile16_masked_count;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tTILE32\t\t{} {}",
-			self.tile32_count, chunk_count
+			"TILE32",
+			self.tile32_count,
+			chunk_count,
+			None,
 		)?;
 		chunk_count += self.tile32_count;
-		writeln!(
+		modid_options.write_section(
 			script,
-			"\t\tTILE32M\t\t{} {}",
-			self.tile32_masked_count, chunk_count
+			"TILE32M",
+			self.tile32_masked_count,
+			chunk_count,
+			None,
 		)?;
 		chunk_count += self.tile32_masked_count;
 
+		if modid_options.emit_comments {
+			for lump in &self.lumps {
+				writeln!(
+					script,
+					"\t\t# Lump: {} (chunks {}-{})",
+					lump.name, lump.start_chunk, lump.end_chunk
+				)?;
+			}
+			if !self.misc_chunks.is_empty() {
+				writeln!(script, "\t\t# Misc chunks start here")?;
+			}
+		}
 		for chunk in &self.misc_chunks {
 			match chunk {
 				MiscChunk::Chunk(name) => {
@@ -325,22 +841,50 @@ impl GfxHeaders {
 		Ok(())
 	}
 
-	fn save_modid_script(&self, filename: &str) -> std::io::Result<()> {
-		let modid_file = std::fs::File::create(filename)?;
+	fn save_modid_script(
+		&self,
+		filename: &str,
+		modid_options: &ModidOptions,
+		create_dirs: bool,
+	) -> std::io::Result<()> {
+		let modid_file = create_output_file(filename, create_dirs)?;
 		let mut modid_writer = std::io::BufWriter::new(modid_file);
-		self.write_modid_script(&mut modid_writer)
+		self.write_modid_script(&mut modid_writer, modid_options)
 	}
 
-	#[cfg(feature = "timestamps")]
-	fn timestamp() -> String {
-		// From man ctime_r: "stores the string in a user-supplied buffer which should have room for at least 26 bytes"
-		let mut buf = vec![0u8; 26];
+	/* Formats a count of seconds since the Unix epoch as "YYYY-MM-DD HH:MM:SS UTC". */
+	fn format_timestamp(unix_secs: u64) -> String {
+		let days = (unix_secs / 86400) as i64;
+		let secs_of_day = unix_secs % 86400;
+
+		/* Convert days since 1970-01-01 to a civil date (Howard Hinnant's algorithm). */
+		let z = days + 719468;
+		let era = z.div_euclid(146097);
+		let doe = z.rem_euclid(146097);
+		let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+		let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+		let mp = (5 * doy + 2) / 153;
+		let day = doy - (153 * mp + 2) / 5 + 1;
+		let month = if mp < 10 { mp + 3 } else { mp - 9 };
+		let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
 
-		let time = unsafe { libc::time(std::ptr::null_mut()) };
-		unsafe { libc::ctime_r(&time, buf.as_mut_ptr() as *mut std::ffi::c_char) };
-		let str_slice = std::ffi::CStr::from_bytes_until_nul(&buf).unwrap();
+		format!(
+			"{:04}-{:02}-{:02} {:02}:{:02}:{:02} UTC",
+			year,
+			month,
+			day,
+			secs_of_day / 3600,
+			(secs_of_day / 60) % 60,
+			secs_of_day % 60
+		)
+	}
 
-		str_slice.to_string_lossy().into_owned()
+	fn timestamp() -> String {
+		let unix_secs = std::time::SystemTime::now()
+			.duration_since(std::time::UNIX_EPOCH)
+			.map(|d| d.as_secs())
+			.unwrap_or(0);
+		GfxHeaders::format_timestamp(unix_secs)
 	}
 
 	fn write_igrab_header(
@@ -348,17 +892,41 @@ impl GfxHeaders {
 		f: &mut dyn std::io::Write,
 		igrab_options: &IGrabOptions,
 	) -> std::io::Result<()> {
+		if igrab_options.pragma_once {
+			writeln!(f, "#pragma once")?;
+		}
 		writeln!(f, "//////////////////////////////////////")?;
 		writeln!(f, "//")?;
-		if let Some(ext) = &self.extension {
+		if let Some(banner) = &igrab_options.custom_banner {
+			writeln!(f, "// {}", banner)?;
+		} else if let Some(ext) = &self.extension {
+			/*
+			 * No '.' here, unlike the assembly header. Both are kept as
+			 * idGrab has always written them, so existing headers don't change.
+			 */
 			writeln!(f, "// Graphics .H file for {}", ext)?;
 		}
-		#[cfg(feature = "timestamps")]
-		write!(f, "// idGrab-ed on {}", GfxHeaders::timestamp())?;
+		if igrab_options.timestamps {
+			writeln!(f, "// idGrab-ed on {}", GfxHeaders::timestamp())?;
+		}
 		writeln!(f, "// idGrab emulating IGRAB {}", igrab_options.version)?;
 		writeln!(f, "//")?;
 		writeln!(f, "//////////////////////////////////////\n")?;
 
+		if igrab_options.emit_gameext_define {
+			if let Some(ext) = &self.extension {
+				writeln!(f, "#define GAMEEXT \"{}\"\n", c_string_escape(ext))?;
+			}
+		}
+
+		if igrab_options.emit_font_defines {
+			let fonts = self.fonts.iter().chain(&self.fonts_masked);
+			for (font_id, font) in (self.fonts_start()..).zip(fonts) {
+				igrab_options.write_define_line(f, font, None, font_id)?;
+			}
+			writeln!(f)?;
+		}
+
 		let mut chunk_id = self.bitmaps_start();
 
 		/* If the IGRAB version is 0.24, we use defines. Otherwise, we use an enum. */
@@ -366,7 +934,14 @@ impl GfxHeaders {
 			writeln!(f, "typedef enum {{")?;
 		}
 
-		/* Fonts are not included, nor masked fonts. */
+		/*
+		 * In the 0.4 enum, only the first entry, and any entry which doesn't
+		 * immediately follow the one before it (e.g., the first extern, after
+		 * the tiles), needs an explicit value.
+		 */
+		let mut next_implicit_id: Option<u32> = None;
+
+		/* Fonts are not included, nor masked fonts, unless emit_font_defines is set. */
 
 		for pic in &self.bitmaps {
 			igrab_options.write_chunk_line(
@@ -374,12 +949,13 @@ impl GfxHeaders {
 				pic,
 				Some("PIC"),
 				chunk_id,
-				chunk_id == self.bitmaps_start(),
+				next_implicit_id != Some(chunk_id),
 			)?;
 			chunk_id += 1;
+			next_implicit_id = Some(chunk_id);
 		}
 
-		writeln!(f, "")?;
+		writeln!(f)?;
 
 		for picm in &self.bitmaps_masked {
 			igrab_options.write_chunk_line(
@@ -387,12 +963,13 @@ impl GfxHeaders {
 				picm,
 				Some("PICM"),
 				chunk_id,
-				chunk_id == self.bitmaps_masked_start(),
+				next_implicit_id != Some(chunk_id),
 			)?;
 			chunk_id += 1;
+			next_implicit_id = Some(chunk_id);
 		}
 
-		writeln!(f, "")?;
+		writeln!(f)?;
 
 		for sprite in &self.sprites {
 			igrab_options.write_chunk_line(
@@ -400,12 +977,43 @@ impl GfxHeaders {
 				sprite,
 				Some("SPR"),
 				chunk_id,
-				chunk_id == self.sprites_start(),
+				next_implicit_id != Some(chunk_id),
 			)?;
 			chunk_id += 1;
+			next_implicit_id = Some(chunk_id);
 		}
 
-		if igrab_options.version == IGrabVersion::ZeroPointFour {
+		if self.named_tile16s().next().is_some() {
+			writeln!(f)?;
+		}
+		for (tile_id, tile) in self.named_tile16s() {
+			igrab_options.write_chunk_line(
+				f,
+				&format!("{}_TILE16", tile),
+				None,
+				tile_id,
+				next_implicit_id != Some(tile_id),
+			)?;
+			next_implicit_id = Some(tile_id + 1);
+		}
+
+		if self.named_tile32s().next().is_some() {
+			writeln!(f)?;
+		}
+		for (tile_id, tile) in self.named_tile32s() {
+			igrab_options.write_chunk_line(
+				f,
+				&format!("{}_TILE32", tile),
+				None,
+				tile_id,
+				next_implicit_id != Some(tile_id),
+			)?;
+			next_implicit_id = Some(tile_id + 1);
+		}
+
+		if igrab_options.version == IGrabVersion::ZeroPointFour
+			&& igrab_options.misc_position == MiscPosition::InEnum
+		{
 			//writeln!(f, "\n// Misc chunks (externs)")?;
 			chunk_id = self.misc_start();
 			for misc in &self.misc_chunks {
@@ -415,7 +1023,11 @@ impl GfxHeaders {
 					| MiscChunk::Article(name)
 					| MiscChunk::Terminator(name) => {
 						igrab_options.write_chunk_line(
-							f, name, None, chunk_id, true,
+							f,
+							name,
+							None,
+							chunk_id,
+							next_implicit_id != Some(chunk_id),
 						)?;
 					}
 					MiscChunk::Demo(num) => {
@@ -437,56 +1049,121 @@ impl GfxHeaders {
 					}
 				}
 				chunk_id += 1;
+				next_implicit_id = Some(chunk_id);
 			}
 		}
 		if igrab_options.version == IGrabVersion::ZeroPointFour {
 			writeln!(f, "\t\tENUMEND\n\t     }} graphicnums;\n")?;
 		}
 
+		if self.tile8_names.iter().any(|name| !name.is_empty()) {
+			writeln!(f, "//\n// Tile8 offsets (within the tile8 chunk)\n//")?;
+			for (offset, tile) in self.named_tile8s() {
+				igrab_options.write_define_line(
+					f,
+					&format!("{}_TILE8_OFFSET", tile),
+					None,
+					offset,
+				)?;
+			}
+		}
+
 		writeln!(f, "//\n// Data LUMPs\n//")?;
-		// Keen doesn't actually define this in the GFX header, so it's commented out.
-		//writeln!(f, "//#define NUMLUMPS {}", self.lumps.len())?;
+		// Keen doesn't actually define this in the GFX header, so it's optional.
+		if igrab_options.emit_numlumps {
+			writeln!(f, "#define NUMLUMPS {}", self.lumps.len())?;
+		}
 		for lump in &self.lumps {
 			writeln!(f, "#define {}_LUMP_START {}", lump.name, lump.start_chunk)?;
 			writeln!(f, "#define {}_LUMP_END {}", lump.name, lump.end_chunk)?;
 		}
 
 		writeln!(f, "//\n// Amount of each data item\n//")?;
-		writeln!(f, "#define NUMCHUNKS    {}", self.num_chunks())?;
-		writeln!(f, "#define NUMFONT      {}", self.fonts.len())?;
-		writeln!(f, "#define NUMFONTM     {}", self.fonts_masked.len())?;
-		writeln!(f, "#define NUMPICS      {}", self.bitmaps.len())?;
-		writeln!(f, "#define NUMPICM      {}", self.bitmaps_masked.len())?;
-		writeln!(f, "#define NUMSPRITES   {}", self.sprites.len())?;
-		writeln!(f, "#define NUMTILE8     {}", self.tile8_count)?;
-		writeln!(f, "#define NUMTILE8M    {}", self.tile8_masked_count)?;
-		writeln!(f, "#define NUMTILE16    {}", self.tile16_count)?;
-		writeln!(f, "#define NUMTILE16M   {}", self.tile16_masked_count)?;
-		writeln!(f, "#define NUMTILE32    {}", self.tile32_count)?;
-		writeln!(f, "#define NUMTILE32M   {}", self.tile32_masked_count)?;
+		let mut amount_defines = vec![
+			("NUMCHUNKS", self.num_chunks()),
+			("NUMFONT", self.fonts.len() as u32),
+			("NUMFONTM", self.fonts_masked.len() as u32),
+			("NUMPICS", self.bitmaps.len() as u32),
+			("NUMPICM", self.bitmaps_masked.len() as u32),
+			("NUMSPRITES", self.sprites.len() as u32),
+			("NUMTILE8", self.tile8_count),
+			("NUMTILE8M", self.tile8_masked_count),
+			("NUMTILE16", self.tile16_count),
+			("NUMTILE16M", self.tile16_masked_count),
+			("NUMTILE32", self.tile32_count),
+			("NUMTILE32M", self.tile32_masked_count),
+		];
+		if igrab_options.emit_numexterns {
+			amount_defines.push(("NUMEXTERNS", self.misc_chunks.len() as u32));
+		}
+		if igrab_options.emit_num_demos && self.demo_count() != 0 {
+			amount_defines.push(("NUMDEMOSAVES", self.demo_count()));
+		}
+		for (label, value) in amount_defines {
+			write!(f, "#define {:12} {}", label, value)?;
+			if value == 0 && igrab_options.annotate_zero_counts {
+				write!(f, " // (none defined)")?;
+			}
+			writeln!(f)?;
+		}
+
+		if igrab_options.misc_position == MiscPosition::AfterAmounts
+			&& !self.misc_chunks.is_empty()
+		{
+			writeln!(f, "//\n// Misc chunks (externs)\n//")?;
+			for (chunk_id, misc) in (self.misc_start()..).zip(&self.misc_chunks) {
+				let name = match misc {
+					MiscChunk::Chunk(name)
+					| MiscChunk::B8000Text(name)
+					| MiscChunk::Article(name)
+					| MiscChunk::Terminator(name) => name.clone(),
+					MiscChunk::Demo(num) => format!("DEMO{}", num),
+				};
+				igrab_options.write_define_line(f, &name, None, chunk_id)?;
+			}
+		}
 
 		writeln!(f, "//\n// File offsets for data items\n//")?;
-		writeln!(f, "#define STRUCTPIC    0")?;
-		writeln!(f, "#define STRUCTPICM   1")?;
-		writeln!(f, "#define STRUCTSPRITE 2")?;
-		writeln!(f, "")?;
-		writeln!(f, "#define STARTFONT    {}", self.fonts_start())?;
-		writeln!(f, "#define STARTFONTM   {}", self.fonts_masked_start())?;
-		writeln!(f, "#define STARTPICS    {}", self.bitmaps_start())?;
-		writeln!(f, "#define STARTPICM    {}", self.bitmaps_masked_start())?;
-		writeln!(f, "#define STARTSPRITES {}", self.sprites_start())?;
-		writeln!(f, "#define STARTTILE8   {}", self.tile8_start())?;
-		writeln!(f, "#define STARTTILE8M  {}", self.tile8_masked_start())?;
-		writeln!(f, "#define STARTTILE16  {}", self.tile16_start())?;
-		writeln!(f, "#define STARTTILE16M {}", self.tile16_masked_start())?;
-		writeln!(f, "#define STARTTILE32  {}", self.tile32_start())?;
-		writeln!(f, "#define STARTTILE32M {}", self.tile32_masked_start())?;
-		writeln!(f, "#define STARTEXTERNS {}", self.misc_start())?;
-
-		writeln!(f, "")?;
-		writeln!(f, "//")?;
-		writeln!(f, "// Thank you for using idGrab!")?;
-		writeln!(f, "//")?;
+		if igrab_options.emit_struct_offsets && self.header_chunk_count != 0 {
+			/* Only the header chunks the script has are named. */
+			let struct_chunks = STRUCT_CHUNK_NAMES
+				.iter()
+				.take(self.header_chunk_count as usize);
+			for (chunk_id, name) in struct_chunks.enumerate() {
+				writeln!(f, "#define {:12} {}", name, chunk_id)?;
+			}
+			writeln!(f)?;
+		}
+		let start_defines = self.start_defines(igrab_options);
+		/* Line the values up after the longest label. */
+		let label_width = start_defines
+			.iter()
+			.map(|(label, _)| label.len())
+			.max()
+			.unwrap();
+		for (label, value) in start_defines.iter() {
+			writeln!(f, "#define {:width$} {}", label, value, width = label_width)?;
+		}
+		if !igrab_options.extra_defines.is_empty() {
+			writeln!(f)?;
+			for (name, value) in igrab_options.extra_defines.iter() {
+				writeln!(f, "#define {} {}", name, value)?;
+			}
+		}
+
+		if igrab_options.hybrid_c_asm {
+			/* The assembly versions go in a C comment, so the file is still valid C. */
+			writeln!(f, "\n/*")?;
+			self.write_igrab_asm_definitions(f, igrab_options)?;
+			writeln!(f, "*/")?;
+		}
+
+		if !igrab_options.suppress_thank_you {
+			writeln!(f)?;
+			writeln!(f, "//")?;
+			writeln!(f, "// Thank you for using idGrab!")?;
+			writeln!(f, "//")?;
+		}
 
 		Ok(())
 	}
@@ -495,8 +1172,9 @@ impl GfxHeaders {
 		&self,
 		filename: &str,
 		igrab_options: &IGrabOptions,
+		create_dirs: bool,
 	) -> std::io::Result<()> {
-		let igrab_file = std::fs::File::create(filename)?;
+		let igrab_file = create_output_file(filename, create_dirs)?;
 		let mut igrab_writer = std::io::BufWriter::new(igrab_file);
 		self.write_igrab_header(&mut igrab_writer, igrab_options)
 	}
@@ -505,40 +1183,101 @@ impl GfxHeaders {
 		f: &mut dyn std::io::Write,
 		igrab_options: &IGrabOptions,
 	) -> std::io::Result<()> {
-		writeln!(f, ";=====================================")?;
-		writeln!(f, ";")?;
-		if let Some(ext) = &self.extension {
-			writeln!(f, "; Graphics .H file for .{}", ext)?;
+		let comment = igrab_options.asm_comment();
+		writeln!(f, "{}=====================================", comment)?;
+		writeln!(f, "{}", comment)?;
+		if let Some(banner) = &igrab_options.custom_banner {
+			writeln!(f, "{} {}", comment, banner)?;
+		} else if let Some(ext) = &self.extension {
+			/*
+			 * The extension is written as a file extension (".CK4"), as idGrab
+			 * always has. Whether IGRAB does the same is unverified. The C
+			 * header leaves the '.' out.
+			 */
+			writeln!(f, "{} Graphics .H file for .{}", comment, ext)?;
+		}
+		if igrab_options.timestamps {
+			writeln!(f, "{} idGrab-ed on {}", comment, GfxHeaders::timestamp())?;
 		}
-		#[cfg(feature = "timestamps")]
-		write!(f, "; idGrab-ed on {}", GfxHeaders::timestamp())?;
-		writeln!(f, "; idGrab emulating IGRAB {}", igrab_options.version)?;
-		writeln!(f, ";")?;
-		writeln!(f, ";=====================================\n")?;
+		writeln!(
+			f,
+			"{} idGrab emulating IGRAB {}",
+			comment, igrab_options.version
+		)?;
+		writeln!(f, "{}", comment)?;
+		writeln!(f, "{}=====================================\n", comment)?;
+
+		self.write_igrab_asm_definitions(f, igrab_options)?;
+
+		if !igrab_options.suppress_thank_you {
+			writeln!(f)?;
+			writeln!(f, "{}", comment)?;
+			writeln!(f, "{} Thank you for using idGrab!", comment)?;
+			writeln!(f, "{}", comment)?;
+		}
+
+		Ok(())
+	}
 
+	/* Writes the body of an assembly header: everything but the banner and closing comments. */
+	fn write_igrab_asm_definitions(
+		&self,
+		f: &mut dyn std::io::Write,
+		igrab_options: &IGrabOptions,
+	) -> std::io::Result<()> {
+		let comment = igrab_options.asm_comment();
 		let mut chunk_id = self.bitmaps_start();
 
 		/* Fonts are not included, nor masked fonts. */
 
+		/* Only sections with something in them get a blank line after them. */
 		for pic in &self.bitmaps {
 			igrab_options.write_asm_chunk_line(f, pic, Some("PIC"), chunk_id)?;
 			chunk_id += 1;
 		}
 
-		writeln!(f, "")?;
+		if !self.bitmaps.is_empty() {
+			writeln!(f)?;
+		}
 
 		for picm in &self.bitmaps_masked {
 			igrab_options.write_asm_chunk_line(f, picm, Some("PICM"), chunk_id)?;
 			chunk_id += 1;
 		}
 
-		writeln!(f, "")?;
+		if !self.bitmaps_masked.is_empty() {
+			writeln!(f)?;
+		}
 
 		for sprite in &self.sprites {
 			igrab_options.write_asm_chunk_line(f, sprite, Some("SPR"), chunk_id)?;
 			chunk_id += 1;
 		}
 
+		if self.named_tile16s().next().is_some() {
+			writeln!(f)?;
+		}
+		for (tile_id, tile) in self.named_tile16s() {
+			igrab_options.write_asm_chunk_line(
+				f,
+				&format!("{}_TILE16", tile),
+				None,
+				tile_id,
+			)?;
+		}
+
+		if self.named_tile32s().next().is_some() {
+			writeln!(f)?;
+		}
+		for (tile_id, tile) in self.named_tile32s() {
+			igrab_options.write_asm_chunk_line(
+				f,
+				&format!("{}_TILE32", tile),
+				None,
+				tile_id,
+			)?;
+		}
+
 		if igrab_options.version == IGrabVersion::ZeroPointFour {
 			//writeln!(f, "\n// Misc chunks (externs)")?;
 			chunk_id = self.misc_start();
@@ -553,58 +1292,85 @@ impl GfxHeaders {
 						)?;
 					}
 					MiscChunk::Demo(num) => {
-						writeln!(f, "DEMO{}  \t=\t{}", num, chunk_id)?;
+						igrab_options.write_asm_equ(
+							f,
+							&format!("DEMO{}  ", num),
+							chunk_id,
+						)?;
 					}
 				}
 				chunk_id += 1;
 			}
 		}
 
-		writeln!(f, "")?;
-		// Keen doesn't actually define this in the GFX header, so it's commented out.
-		//writeln!(f, "//#define NUMLUMPS {}", self.lumps.len())?;
+		let wrote_chunks = chunk_id != self.bitmaps_start()
+			|| self.named_tile16s().next().is_some()
+			|| self.named_tile32s().next().is_some();
+		if wrote_chunks {
+			writeln!(f)?;
+		}
+		for (offset, tile) in self.named_tile8s() {
+			igrab_options.write_asm_equ(
+				f,
+				&format!("{}_TILE8_OFFSET  ", tile),
+				offset,
+			)?;
+		}
+		// Keen doesn't actually define this in the GFX header, so it's optional.
+		if igrab_options.emit_numlumps {
+			igrab_options.write_asm_equ(f, "NUMLUMPS  ", self.lumps.len())?;
+		}
 		for lump in &self.lumps {
-			writeln!(f, "{}_LUMP_START  \t=\t{}", lump.name, lump.start_chunk)?;
-			writeln!(f, "{}_LUMP_END  \t=\t{}", lump.name, lump.end_chunk)?;
-		}
-
-		writeln!(f, ";\n; Amount of each data item\n;")?;
-		writeln!(f, "NUMCHUNKS\t=\t{}", self.num_chunks())?;
-		writeln!(f, "NUMFONT  \t=\t{}", self.fonts.len())?;
-		writeln!(f, "NUMFONTM  \t=\t{}", self.fonts_masked.len())?;
-		writeln!(f, "NUMPICS  \t=\t{}", self.bitmaps.len())?;
-		writeln!(f, "NUMPICM  \t=\t{}", self.bitmaps_masked.len())?;
-		writeln!(f, "NUMSPRITES  \t=\t{}", self.sprites.len())?;
-		writeln!(f, "NUMTILE8  \t=\t{}", self.tile8_count)?;
-		writeln!(f, "NUMTILE8M  \t=\t{}", self.tile8_masked_count)?;
-		writeln!(f, "NUMTILE16  \t=\t{}", self.tile16_count)?;
-		writeln!(f, "NUMTILE16M  \t=\t{}", self.tile16_masked_count)?;
-		writeln!(f, "NUMTILE32  \t=\t{}", self.tile32_count)?;
-		writeln!(f, "NUMTILE32M  \t=\t{}", self.tile32_masked_count)?;
-
-		writeln!(f, ";\n; File offsets for data items\n;")?;
-		writeln!(f, "STRUCTPIC  \t=\t0")?;
-		writeln!(f, "STRUCTPICM  \t=\t1")?;
-		writeln!(f, "STRUCTSPRITE  \t=\t2")?;
-		writeln!(f, "")?;
-		writeln!(f, "STARTFONT  \t=\t{}", self.fonts_start())?;
-		writeln!(f, "STARTFONTM  \t=\t{}", self.fonts_masked_start())?;
-		writeln!(f, "STARTPICS  \t=\t{}", self.bitmaps_start())?;
-		writeln!(f, "STARTPICM  \t=\t{}", self.bitmaps_masked_start())?;
-		writeln!(f, "STARTSPRITES  \t=\t{}", self.sprites_start())?;
-		writeln!(f, "STARTTILE8  \t=\t{}", self.tile8_start())?;
-		writeln!(f, "STARTTILE8M  \t=\t{}", self.tile8_masked_start())?;
-		writeln!(f, "STARTTILE16  \t=\t{}", self.tile16_start())?;
-		writeln!(f, "STARTTILE16M  \t=\t{}", self.tile16_masked_start())?;
-		writeln!(f, "STARTTILE32  \t=\t{}", self.tile32_start())?;
-		writeln!(f, "STARTTILE32M  \t=\t{}", self.tile32_masked_start())?;
-		writeln!(f, "STARTEXTERNS  \t=\t{}", self.misc_start())?;
-
-		writeln!(f, "")?;
-		writeln!(f, ";")?;
-		writeln!(f, "; Thank you for using idGrab!")?;
-		writeln!(f, ";")?;
+			igrab_options.write_asm_equ(
+				f,
+				&format!("{}_LUMP_START  ", lump.name),
+				lump.start_chunk,
+			)?;
+			igrab_options.write_asm_equ(
+				f,
+				&format!("{}_LUMP_END  ", lump.name),
+				lump.end_chunk,
+			)?;
+		}
+
+		/*
+		 * IGRAB pads every label but NUMCHUNKS with two spaces before the tab.
+		 * This hasn't been checked against real IGRAB output:
+		 * testdata/GFXE_CK4.EQU is idGrab's own, so only catches regressions.
+		 */
+		writeln!(f, "{0}\n{0} Amount of each data item\n{0}", comment)?;
+		igrab_options.write_asm_equ(f, "NUMCHUNKS", self.num_chunks())?;
+		igrab_options.write_asm_equ(f, "NUMFONT  ", self.fonts.len())?;
+		igrab_options.write_asm_equ(f, "NUMFONTM  ", self.fonts_masked.len())?;
+		igrab_options.write_asm_equ(f, "NUMPICS  ", self.bitmaps.len())?;
+		igrab_options.write_asm_equ(f, "NUMPICM  ", self.bitmaps_masked.len())?;
+		igrab_options.write_asm_equ(f, "NUMSPRITES  ", self.sprites.len())?;
+		igrab_options.write_asm_equ(f, "NUMTILE8  ", self.tile8_count)?;
+		igrab_options.write_asm_equ(f, "NUMTILE8M  ", self.tile8_masked_count)?;
+		igrab_options.write_asm_equ(f, "NUMTILE16  ", self.tile16_count)?;
+		igrab_options.write_asm_equ(f, "NUMTILE16M  ", self.tile16_masked_count)?;
+		igrab_options.write_asm_equ(f, "NUMTILE32  ", self.tile32_count)?;
+		igrab_options.write_asm_equ(f, "NUMTILE32M  ", self.tile32_masked_count)?;
+		if igrab_options.emit_numexterns {
+			igrab_options.write_asm_equ(f, "NUMEXTERNS  ", self.misc_chunks.len())?;
+		}
+		if igrab_options.emit_num_demos && self.demo_count() != 0 {
+			igrab_options.write_asm_equ(f, "NUMDEMOSAVES  ", self.demo_count())?;
+		}
 
+		writeln!(f, "{0}\n{0} File offsets for data items\n{0}", comment)?;
+		if igrab_options.emit_struct_offsets && self.header_chunk_count != 0 {
+			let struct_chunks = STRUCT_CHUNK_NAMES
+				.iter()
+				.take(self.header_chunk_count as usize);
+			for (chunk_id, name) in struct_chunks.enumerate() {
+				igrab_options.write_asm_equ(f, &format!("{}  ", name), chunk_id)?;
+			}
+			writeln!(f)?;
+		}
+		for (label, value) in self.start_defines(igrab_options) {
+			igrab_options.write_asm_equ(f, &format!("{}  ", label), value)?;
+		}
 		Ok(())
 	}
 
@@ -612,55 +1378,152 @@ impl GfxHeaders {
 		&self,
 		filename: &str,
 		igrab_options: &IGrabOptions,
+		create_dirs: bool,
 	) -> std::io::Result<()> {
-		let igrab_file = std::fs::File::create(filename)?;
+		let igrab_file = create_output_file(filename, create_dirs)?;
 		let mut igrab_writer = std::io::BufWriter::new(igrab_file);
 		self.write_igrab_asm_header(&mut igrab_writer, igrab_options)
 	}
 
-	fn write_omnispeak_cfg(&self, f: &mut dyn std::io::Write) -> std::io::Result<()> {
-		writeln!(f, "# GFX Header (Omnispeak)\n")?;
+	fn write_omnispeak_cfg(
+		&self,
+		f: &mut dyn std::io::Write,
+		omnispeak_options: &OmnispeakOptions,
+	) -> std::io::Result<()> {
+		if omnispeak_options.emit_generator_comment {
+			match IDGRAB_VERSION {
+				Some(version) => {
+					writeln!(f, "# GFX Header generated by idGrab {}", version)?
+				}
+				None => writeln!(f, "# GFX Header generated by idGrab")?,
+			}
+			if let Some(source) = &omnispeak_options.source_filename {
+				writeln!(f, "# Source: {}", source)?;
+			}
+			writeln!(f)?;
+		}
+		if omnispeak_options.emit_game_ext {
+			if let Some(ext) = &self.extension {
+				/* Escape it as scripts do, so a '"' can't end the string early. */
+				writeln!(f, "%string gameExt {}\n", parser::quote_string(ext))?;
+			}
+		}
+		if self.header_chunk_count == 3 {
+			writeln!(f, "%int STRUCTPIC 0")?;
+			writeln!(f, "%int STRUCTPICM 1")?;
+			writeln!(f, "%int STRUCTSPRITE 2\n")?;
+		} else if self.header_chunk_count != 0 {
+			for chunk_id in 0..self.header_chunk_count {
+				writeln!(
+					f,
+					"%int {}{} {}",
+					omnispeak_options.struct_slot_prefix, chunk_id, chunk_id
+				)?;
+			}
+			writeln!(f)?;
+		}
 		let mut chunk_id = self.header_chunk_count;
 
 		writeln!(f, "# Fonts")?;
-		for font in &self.fonts {
-			writeln!(f, "%int FON_{} {}", font, chunk_id)?;
-			chunk_id += 1;
-		}
+		omnispeak_options.write_section(f, "FON", "font", &self.fonts, chunk_id)?;
+		chunk_id += self.fonts.len() as u32;
 
 		writeln!(f, "# Masked Fonts")?;
-		for font in &self.fonts_masked {
-			writeln!(f, "%int FONM_{} {}", font, chunk_id)?;
-			chunk_id += 1;
-		}
+		omnispeak_options.write_section(
+			f,
+			"FONM",
+			"fontM",
+			&self.fonts_masked,
+			chunk_id,
+		)?;
+		chunk_id += self.fonts_masked.len() as u32;
+
+		if omnispeak_options.emit_font_vars {
+			writeln!(f, "%int NUMFONT {}", self.fonts.len())?;
+			writeln!(f, "%int NUMFONTM {}", self.fonts_masked.len())?;
+			writeln!(f, "%int STARTFONT {}", self.fonts_start())?;
+			writeln!(f, "%int STARTFONTM {}", self.fonts_masked_start())?;
+		}
+		if omnispeak_options.emit_font_section_vars {
+			writeln!(f, "%int NUMFONTS {}", self.fonts.len())?;
+			writeln!(f, "%int FONTS_START {}", self.fonts_start())?;
+			writeln!(f, "%int NUMFONTSM {}", self.fonts_masked.len())?;
+			writeln!(f, "%int FONTSM_START {}", self.fonts_masked_start())?;
+		}
 
 		writeln!(f, "# Bitmaps")?;
-		for pic in &self.bitmaps {
-			writeln!(f, "%int PIC_{} {}", pic, chunk_id)?;
-			chunk_id += 1;
+		omnispeak_options.write_section(f, "PIC", "pic", &self.bitmaps, chunk_id)?;
+		chunk_id += self.bitmaps.len() as u32;
+		if omnispeak_options.emit_section_vars {
+			writeln!(f, "%int NUMPICS {}", self.bitmaps.len())?;
+			writeln!(f, "%int STARTPICS {}", self.bitmaps_start())?;
 		}
 
 		writeln!(f, "# Masked Bitmaps")?;
-		for picm in &self.bitmaps_masked {
-			writeln!(f, "%int MPIC_{} {}", picm, chunk_id)?;
-			chunk_id += 1;
+		omnispeak_options.write_section(
+			f,
+			"MPIC",
+			"picM",
+			&self.bitmaps_masked,
+			chunk_id,
+		)?;
+		chunk_id += self.bitmaps_masked.len() as u32;
+		if omnispeak_options.emit_section_vars {
+			writeln!(f, "%int NUMPICM {}", self.bitmaps_masked.len())?;
+			writeln!(f, "%int STARTPICM {}", self.bitmaps_masked_start())?;
 		}
 
 		writeln!(f, "# Sprites")?;
-		for sprite in &self.sprites {
-			writeln!(f, "%int SPR_{} {}", sprite, chunk_id)?;
-			chunk_id += 1;
+		omnispeak_options.write_section(f, "SPR", "sprite", &self.sprites, chunk_id)?;
+		chunk_id += self.sprites.len() as u32;
+		if omnispeak_options.emit_section_vars {
+			writeln!(f, "%int NUMSPRITES {}", self.sprites.len())?;
+			writeln!(f, "%int STARTSPRITES {}", self.sprites_start())?;
+		}
+
+		if omnispeak_options.emit_tile_vars {
+			writeln!(f, "# Tiles")?;
+			writeln!(f, "%int NUMTILE8 {}", self.tile8_count)?;
+			writeln!(f, "%int NUMTILE8M {}", self.tile8_masked_count)?;
+			writeln!(f, "%int NUMTILE16 {}", self.tile16_count)?;
+			writeln!(f, "%int NUMTILE16M {}", self.tile16_masked_count)?;
+			writeln!(f, "%int NUMTILE32 {}", self.tile32_count)?;
+			writeln!(f, "%int NUMTILE32M {}", self.tile32_masked_count)?;
+			writeln!(f, "%int STARTTILE8 {}", self.tile8_start())?;
+			writeln!(f, "%int STARTTILE8M {}", self.tile8_masked_start())?;
+			writeln!(f, "%int STARTTILE16 {}", self.tile16_start())?;
+			writeln!(f, "%int STARTTILE16M {}", self.tile16_masked_start())?;
+			writeln!(f, "%int STARTTILE32 {}", self.tile32_start())?;
+			writeln!(f, "%int STARTTILE32M {}", self.tile32_masked_start())?;
 		}
 
 		/* Tile8 and Tile8m are stored in a single chunk each. */
+		if !self.tile8_names.is_empty() {
+			writeln!(f, "# Named Tiles8 (offsets within the chunk)")?;
+			for (offset, tile) in self.named_tile8s() {
+				writeln!(f, "%int TILE8_{} {}", tile, offset)?;
+			}
+		}
 		chunk_id += if self.tile8_count != 0 { 1 } else { 0 };
 
 		chunk_id += if self.tile8_masked_count != 0 { 1 } else { 0 };
 
+		if self.named_tile16s().next().is_some() {
+			writeln!(f, "# Named Tiles16")?;
+			for (tile_id, tile) in self.named_tile16s() {
+				writeln!(f, "%int TILE16_{} {}", tile, tile_id)?;
+			}
+		}
 		chunk_id += self.tile16_count;
 
 		chunk_id += self.tile16_masked_count;
 
+		if self.named_tile32s().next().is_some() {
+			writeln!(f, "# Named Tiles32")?;
+			for (tile_id, tile) in self.named_tile32s() {
+				writeln!(f, "%int TILE32_{} {}", tile, tile_id)?;
+			}
+		}
 		chunk_id += self.tile32_count;
 
 		chunk_id += self.tile32_masked_count;
@@ -669,16 +1532,20 @@ impl GfxHeaders {
 		for chunk in &self.misc_chunks {
 			match chunk {
 				MiscChunk::Chunk(name) => {
-					writeln!(f, "%int EXTERN_{} {}", name, chunk_id)?;
+					let prefix = &omnispeak_options.chunk_prefix;
+					writeln!(f, "%int {}{} {}", prefix, name, chunk_id)?;
 				}
 				MiscChunk::B8000Text(name) => {
-					writeln!(f, "%int EXTERN_{} {}", name, chunk_id)?;
+					let prefix = &omnispeak_options.b8000text_prefix;
+					writeln!(f, "%int {}{} {}", prefix, name, chunk_id)?;
 				}
 				MiscChunk::Article(name) => {
-					writeln!(f, "%int TEXT_{} {}", name, chunk_id)?;
+					let prefix = &omnispeak_options.article_prefix;
+					writeln!(f, "%int {}{} {}", prefix, name, chunk_id)?;
 				}
 				MiscChunk::Terminator(name) => {
-					writeln!(f, "%int EXTERN_{} {}", name, chunk_id)?;
+					let prefix = &omnispeak_options.terminator_prefix;
+					writeln!(f, "%int {}{} {}", prefix, name, chunk_id)?;
 				}
 				MiscChunk::Demo(num) => {
 					if demostart.is_none() {
@@ -689,21 +1556,40 @@ impl GfxHeaders {
 			}
 			chunk_id += 1;
 		}
-		writeln!(f, "%int DEMOSTART {}", demostart.unwrap())?;
+		/* There's nowhere for demos to start if there aren't any. */
+		if let Some(demostart) = demostart {
+			writeln!(f, "%int DEMOSTART {}", demostart)?;
+			if omnispeak_options.emit_demo_aliases {
+				writeln!(f, "%int STARTDEMO {}", demostart)?;
+				writeln!(f, "%int NUMDEMOS {}", self.demo_count())?;
+			}
+		}
 
 		writeln!(f, "#\n# Lumps\n#")?;
 		writeln!(f, "%int NUMLUMPS {}", self.lumps.len())?;
+		/* Omnispeak doesn't like empty arrays. */
+		if self.lumps.is_empty() {
+			return Ok(());
+		}
+		/* With section arrays, there are no per-chunk variables to refer to. */
+		let lump_chunk_name = |chunk| {
+			if omnispeak_options.use_arrays_for_sections {
+				None
+			} else {
+				self.omnispeak_chunk_name(chunk, omnispeak_options)
+			}
+		};
 		writeln!(f, "%intarray lumpStarts")?;
 		let mut lump_start_iterator = self.lumps.iter().peekable();
 		while let Some(lump) = lump_start_iterator.next() {
-			let start_chunk_name = self.omnispeak_chunk_name(lump.start_chunk);
+			let start_chunk_name = lump_chunk_name(lump.start_chunk);
 			let comma = if lump_start_iterator.peek().is_none() {
 				""
 			} else {
 				","
 			};
-			if start_chunk_name.is_some() {
-				writeln!(f, "\t@{}{}", start_chunk_name.unwrap(), comma)?;
+			if let Some(start_chunk_name) = start_chunk_name {
+				writeln!(f, "\t@{}{}", start_chunk_name, comma)?;
 			} else {
 				writeln!(f, "\t{}{}", lump.start_chunk, comma)?;
 			}
@@ -711,14 +1597,14 @@ impl GfxHeaders {
 		writeln!(f, "%intarray lumpEnds")?;
 		let mut lump_end_iterator = self.lumps.iter().peekable();
 		while let Some(lump) = lump_end_iterator.next() {
-			let end_chunk_name = self.omnispeak_chunk_name(lump.end_chunk);
+			let end_chunk_name = lump_chunk_name(lump.end_chunk);
 			let comma = if lump_end_iterator.peek().is_none() {
 				""
 			} else {
 				","
 			};
-			if end_chunk_name.is_some() {
-				writeln!(f, "\t@{}{}", end_chunk_name.unwrap(), comma)?;
+			if let Some(end_chunk_name) = end_chunk_name {
+				writeln!(f, "\t@{}{}", end_chunk_name, comma)?;
 			} else {
 				writeln!(f, "\t{}{}", lump.end_chunk, comma)?;
 			}
@@ -730,190 +1616,574 @@ impl GfxHeaders {
 		Ok(())
 	}
 
-	fn save_omnispeak_cfg(&self, filename: &str) -> std::io::Result<()> {
-		let omnispeak_file = std::fs::File::create(filename)?;
+	fn save_omnispeak_cfg(
+		&self,
+		filename: &str,
+		omnispeak_options: &OmnispeakOptions,
+		create_dirs: bool,
+	) -> std::io::Result<()> {
+		let omnispeak_file = create_output_file(filename, create_dirs)?;
 		let mut omnispeak_writer = std::io::BufWriter::new(omnispeak_file);
-		self.write_omnispeak_cfg(&mut omnispeak_writer)
+		self.write_omnispeak_cfg(&mut omnispeak_writer, omnispeak_options)
+	}
+
+	fn write_script_section(
+		&self,
+		f: &mut dyn std::io::Write,
+		section: ChunkSection,
+		section_name: &str,
+		names: &[String],
+		start_chunk: u32,
+	) -> std::io::Result<()> {
+		let lumps: Vec<&Lump> =
+			self.lumps.iter().filter(|l| l.section == section).collect();
+		if names.is_empty() && lumps.is_empty() {
+			return Ok(());
+		}
+		writeln!(f, "{} {{", section_name)?;
+		/* One past the last chunk, so empty lumps at the end of the section are written. */
+		let end_chunk = start_chunk + names.len() as u32;
+		for chunk_id in start_chunk..=end_chunk {
+			for lump in lumps.iter().filter(|l| l.start_chunk == chunk_id) {
+				writeln!(f, "Lump {} {{", parser::quote_string(&lump.name))?;
+				// Empty lumps end before they start.
+				if lump.end_chunk < lump.start_chunk {
+					writeln!(f, "}}")?;
+				}
+			}
+			if let Some(name) = names.get((chunk_id - start_chunk) as usize) {
+				writeln!(f, "\t{}", parser::quote_string(name))?;
+				if lumps.iter().any(|l| {
+					l.end_chunk == chunk_id && l.start_chunk <= chunk_id
+				}) {
+					writeln!(f, "}}")?;
+				}
+			}
+		}
+		writeln!(f, "}}")?;
+		writeln!(f)
+	}
+
+	/*
+	 * Writes the headers back out as an idGrab script. Sections are written in
+	 * chunk order, with the Extension first, whatever order the original
+	 * script used.
+	 */
+	fn write_script(&self, f: &mut dyn std::io::Write) -> std::io::Result<()> {
+		if let Some(ext) = &self.extension {
+			writeln!(f, "Extension {}", parser::quote_string(ext))?;
+		}
+		if self.game != GameVariant::Custom {
+			writeln!(f, "Game {}", self.game)?;
+		}
+		let default_header_chunk_count =
+			self.game.default_header_chunk_count().unwrap_or(3);
+		if self.header_chunk_count != default_header_chunk_count {
+			writeln!(f, "HeaderChunks {}", self.header_chunk_count)?;
+		}
+		if self.extension.is_some()
+			|| self.game != GameVariant::Custom
+			|| self.header_chunk_count != default_header_chunk_count
+		{
+			writeln!(f)?;
+		}
+		self.write_script_section(
+			f,
+			ChunkSection::Fonts,
+			"Fonts",
+			&self.fonts,
+			self.fonts_start(),
+		)?;
+		self.write_script_section(
+			f,
+			ChunkSection::FontsMasked,
+			"FontsMasked",
+			&self.fonts_masked,
+			self.fonts_masked_start(),
+		)?;
+		self.write_script_section(
+			f,
+			ChunkSection::Bitmaps,
+			"Bitmaps",
+			&self.bitmaps,
+			self.bitmaps_start(),
+		)?;
+		self.write_script_section(
+			f,
+			ChunkSection::BitmapsMasked,
+			"BitmapsMasked",
+			&self.bitmaps_masked,
+			self.bitmaps_masked_start(),
+		)?;
+		self.write_script_section(
+			f,
+			ChunkSection::Sprites,
+			"Sprites",
+			&self.sprites,
+			self.sprites_start(),
+		)?;
+
+		let tile_counts = [
+			("Tiles8", self.tile8_count),
+			("Tiles8Masked", self.tile8_masked_count),
+			("Tiles16", self.tile16_count),
+			("Tiles16Masked", self.tile16_masked_count),
+			("Tiles32", self.tile32_count),
+			("Tiles32Masked", self.tile32_masked_count),
+		];
+		let mut wrote_tiles = false;
+		for (keyword, count) in tile_counts.iter() {
+			let names = match *keyword {
+				"Tiles8" if !self.tile8_names.is_empty() => Some(&self.tile8_names),
+				"Tiles16" => self.tile16_names.as_ref(),
+				"Tiles32" => self.tile32_names.as_ref(),
+				_ => None,
+			};
+			if let Some(names) = names {
+				writeln!(f, "{} {{", keyword)?;
+				for name in names {
+					writeln!(f, "\t{}", parser::quote_string(name))?;
+				}
+				writeln!(f, "}}")?;
+				wrote_tiles = true;
+			} else if *count != 0 {
+				writeln!(f, "{} {}", keyword, count)?;
+				wrote_tiles = true;
+			}
+		}
+		if wrote_tiles {
+			writeln!(f)?;
+		}
+
+		for misc in &self.misc_chunks {
+			match misc {
+				MiscChunk::Chunk(name) => {
+					writeln!(f, "Chunk {}", parser::quote_string(name))?
+				}
+				MiscChunk::Article(name) => {
+					writeln!(f, "Article {}", parser::quote_string(name))?
+				}
+				MiscChunk::B8000Text(name) => {
+					writeln!(f, "B8000Text {}", parser::quote_string(name))?
+				}
+				MiscChunk::Terminator(name) => {
+					writeln!(f, "Terminator {}", parser::quote_string(name))?
+				}
+				MiscChunk::Demo(num) => writeln!(f, "Demo {}", num)?,
+			}
+		}
+		Ok(())
+	}
+
+	fn save_script(&self, filename: &str, create_dirs: bool) -> std::io::Result<()> {
+		let script_file = create_output_file(filename, create_dirs)?;
+		let mut script_writer = std::io::BufWriter::new(script_file);
+		self.write_script(&mut script_writer)
 	}
 }
 
-fn parse_gfx_script(filename: &str) -> std::io::Result<GfxHeaders> {
-	let script_data = std::fs::read_to_string(filename)?;
-	let mut lexer = parser::Lexer::from_str(script_data.as_str());
+fn parse_gfx_script(filename: impl AsRef<Path>) -> ParseResult<GfxHeaders> {
+	parse_gfx_script_with_options(filename, parser::ParserOptions::default())
+}
+
+fn parse_gfx_script_with_options(
+	filename: impl AsRef<Path>,
+	parser_options: parser::ParserOptions,
+) -> ParseResult<GfxHeaders> {
+	let filename = filename.as_ref();
+	let script_data = std::fs::read_to_string(filename).map_err(|e| {
+		Box::new(ParseError {
+			location: filename.display().to_string(),
+			line: 0,
+			expected: None,
+			found: None,
+			message: e.to_string(),
+			line_content: None,
+		})
+	})?;
+	parse_gfx_lexer(
+		parser::Lexer::from_str(&script_data)
+			.with_filename(filename)
+			.with_options(parser_options),
+	)
+}
+
+const MISC_CHUNK_KEYWORDS: &[&str] = &["Chunk", "Article", "B8000Text", "Terminator", "Demo"];
+
+/* Parses the argument to one of the MISC_CHUNK_KEYWORDS. */
+fn parse_misc_chunk(lexer: &mut parser::Lexer, keyword: &str) -> ParseResult<MiscChunk> {
+	Ok(match keyword {
+		"Chunk" => MiscChunk::Chunk(lexer.get_string_literal()?),
+		"Article" => MiscChunk::Article(lexer.get_string_literal()?),
+		"B8000Text" => MiscChunk::B8000Text(lexer.get_string_literal()?),
+		"Terminator" => MiscChunk::Terminator(lexer.get_string_literal()?),
+		"Demo" => MiscChunk::Demo(lexer.get_count_literal()?),
+		_ => panic!("{} is not a misc chunk keyword!", keyword),
+	})
+}
+
+fn duplicate_name_message(name: &str, section: ChunkSection, first_line: usize) -> String {
+	format!(
+		"Duplicate name \"{}\" in the {} (first used on line {})!",
+		name, section, first_line
+	)
+}
+
+/*
+ * Fails if a name has already been used in the same section. Names in
+ * different sections get different suffixes (TITLEPIC and TITLESPR), so can
+ * be the same.
+ */
+fn check_duplicate_name(
+	lexer: &parser::Lexer,
+	seen_names: &mut HashMap<(ChunkSection, String), usize>,
+	section: ChunkSection,
+	name: &str,
+) -> ParseResult<()> {
+	match seen_names.get(&(section, name.to_string())) {
+		Some(&first_line) => {
+			Err(lexer.error(duplicate_name_message(name, section, first_line)))
+		}
+		None => {
+			seen_names.insert((section, name.to_string()), lexer.last_line());
+			Ok(())
+		}
+	}
+}
+
+/*
+ * The last chunk of a lump, given the chunk after it. Empty lumps end before
+ * they start, so an empty lump at chunk 0 has no end chunk at all.
+ */
+fn lump_end_chunk(lexer: &parser::Lexer, lump: &Lump, next_chunk: u32) -> ParseResult<u32> {
+	next_chunk.checked_sub(1).ok_or_else(|| {
+		lexer.error(format!(
+			"Lump \"{}\" is empty, and starts at chunk 0.",
+			lump.name
+		))
+	})
+}
+
+/*
+ * Parses the argument to a TilesN keyword: either a count, or a block naming
+ * each tile ("" for unnamed ones). Returns the number of tiles, and their
+ * names if there was a block.
+ */
+fn parse_tile_count(lexer: &mut parser::Lexer) -> ParseResult<(u32, Option<Vec<String>>)> {
+	let tiles_tok = lexer.next_token()?;
+	if tiles_tok != Some(parser::Token::Symbol('{')) {
+		if let Some(token) = tiles_tok {
+			lexer.unget_token(token);
+		}
+		return Ok((lexer.get_count_literal()?, None));
+	}
+	let mut names = Vec::new();
+	loop {
+		match lexer.next_token()? {
+			Some(parser::Token::Symbol('}')) | None => {
+				break;
+			}
+			Some(parser::Token::StringLiteral(tile_name)) => {
+				names.push(tile_name);
+			}
+			tok => {
+				return Err(lexer.unknown_token(tok.as_ref()));
+			}
+		}
+	}
+	Ok((names.len() as u32, Some(names)))
+}
+
+/*
+ * Parses a script from a string. Top-level entries may appear in any order:
+ * in particular, the Extension doesn't need to come before the sections.
+ */
+fn parse_gfx_script_str(script_data: &str) -> ParseResult<GfxHeaders> {
+	parse_gfx_lexer(parser::Lexer::from_str(script_data))
+}
+
+fn parse_gfx_lexer(mut lexer: parser::Lexer) -> ParseResult<GfxHeaders> {
 	let mut current_lump: Option<Lump> = None;
+	/* Set by HeaderChunks, which overrides the Game's default wherever it is. */
+	let mut explicit_header_chunk_count = false;
+	/* The line every name was first defined on, by section. */
+	let mut seen_names = HashMap::new();
 
-	let mut headers = GfxHeaders::default();
-	headers.header_chunk_count = 3;
+	let mut headers = GfxHeaders {
+		header_chunk_count: 3,
+		..Default::default()
+	};
 
 	loop {
-		let entry_type = lexer.next_token();
-		match entry_type {
+		let keyword = match lexer.next_token()? {
 			None => {
 				break;
 			}
-			Some(parser::Token::Ident("Extension")) => {
-				headers.extension = Some(lexer.get_string_literal());
+			Some(parser::Token::Ident(keyword)) => keyword,
+			tok => {
+				return Err(lexer.unknown_token(tok.as_ref()));
 			}
-			Some(parser::Token::Ident("Fonts")) => {
-				lexer.expect_symbol('{');
+		};
+		match keyword.as_str() {
+			"Extension" => {
+				headers.extension = Some(lexer.get_string_literal()?);
+			}
+			"Game" => {
+				let game_name = match lexer.next_token()? {
+					Some(parser::Token::Ident(name)) => name,
+					Some(parser::Token::StringLiteral(name)) => name,
+					tok => {
+						return Err(
+							lexer.unexpected("game name", tok.as_ref())
+						)
+					}
+				};
+				headers.game =
+					GameVariant::from_name(&game_name).ok_or_else(|| {
+						lexer.error(format!("Unknown game {}!", game_name))
+					})?;
+				/* Only fill in what an earlier keyword hasn't already set. */
+				if headers.extension.is_none() {
+					headers.extension = headers
+						.game
+						.default_extension()
+						.map(|e| e.to_string());
+				}
+				if let Some(count) = headers.game.default_header_chunk_count() {
+					if !explicit_header_chunk_count {
+						headers.header_chunk_count = count;
+					}
+				}
+			}
+			"HeaderChunks" => {
+				headers.header_chunk_count = lexer.get_count_literal()?;
+				explicit_header_chunk_count = true;
+			}
+			"Fonts" => {
+				lexer.expect_symbol('{')?;
 				loop {
-					let font_tok = lexer.next_token();
+					let font_tok = lexer.next_token()?;
 					match font_tok {
 						Some(parser::Token::Symbol('}')) => {
 							if current_lump.is_some() {
 								let mut lump = current_lump
 									.take()
 									.unwrap();
-								lump.end_chunk = headers
+								let next_chunk = headers
 									.fonts_start()
 									+ headers.fonts.len()
-										as u32 - 1;
+										as u32;
+								lump.end_chunk = lump_end_chunk(
+									&lexer, &lump, next_chunk,
+								)?;
 								headers.lumps.push(lump);
 							} else {
 								break;
 							}
 						}
-						Some(parser::Token::Ident("Lump")) => {
+						Some(parser::Token::Ident(ref ident))
+							if ident == "Lump" =>
+						{
 							if current_lump.is_some() {
-								panic!("Tried to nest a lump!");
+								return Err(lexer.error(
+									"Tried to nest a lump!"
+										.to_string(),
+								));
 							}
 							current_lump = Some(Lump {
-								name: lexer.get_string_literal(),
+								defined_at_line: lexer.last_line(),
+								section: ChunkSection::Fonts,
+								name: lexer.get_string_literal()?,
 								start_chunk: headers.fonts_start()
 									+ headers.fonts.len()
 										as u32,
 								end_chunk: 0,
 							});
-							lexer.expect_symbol('{');
+							lexer.expect_symbol('{')?;
 						}
 						Some(parser::Token::StringLiteral(font_name)) => {
+							check_duplicate_name(
+								&lexer,
+								&mut seen_names,
+								ChunkSection::Fonts,
+								&font_name,
+							)?;
 							headers.fonts.push(font_name);
 						}
 						None => {
 							break;
 						}
-						_ => {
-							panic!("Unknown token");
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
 						}
 					}
 				}
 			}
-			Some(parser::Token::Ident("FontsMasked")) => {
-				lexer.expect_symbol('{');
+			"FontsMasked" => {
+				lexer.expect_symbol('{')?;
 				loop {
-					let font_tok = lexer.next_token();
+					let font_tok = lexer.next_token()?;
 					match font_tok {
 						Some(parser::Token::Symbol('}')) => {
 							if current_lump.is_some() {
 								let mut lump = current_lump
 									.take()
 									.unwrap();
-								lump.end_chunk = headers
+								let next_chunk = headers
 									.fonts_masked_start()
 									+ headers.fonts_masked.len()
-										as u32 - 1;
+										as u32;
+								lump.end_chunk = lump_end_chunk(
+									&lexer, &lump, next_chunk,
+								)?;
 								headers.lumps.push(lump);
 							} else {
 								break;
 							}
 						}
-						Some(parser::Token::Ident("Lump")) => {
+						Some(parser::Token::Ident(ref ident))
+							if ident == "Lump" =>
+						{
 							if current_lump.is_some() {
-								panic!("Tried to nest a lump!");
+								return Err(lexer.error(
+									"Tried to nest a lump!"
+										.to_string(),
+								));
 							}
 							current_lump = Some(Lump {
-								name: lexer.get_string_literal(),
+								defined_at_line: lexer.last_line(),
+								section: ChunkSection::FontsMasked,
+								name: lexer.get_string_literal()?,
 								start_chunk: headers
 									.fonts_masked_start()
 									+ headers.fonts_masked.len()
 										as u32,
 								end_chunk: 0,
 							});
-							lexer.expect_symbol('{');
+							lexer.expect_symbol('{')?;
 						}
 						Some(parser::Token::StringLiteral(font_name)) => {
+							check_duplicate_name(
+								&lexer,
+								&mut seen_names,
+								ChunkSection::FontsMasked,
+								&font_name,
+							)?;
 							headers.fonts_masked.push(font_name);
 						}
 						None => {
 							break;
 						}
-						_ => {
-							panic!("Unknown token");
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
 						}
 					}
 				}
 			}
-			Some(parser::Token::Ident("Bitmaps")) => {
-				lexer.expect_symbol('{');
+			"Bitmaps" => {
+				lexer.expect_symbol('{')?;
 				loop {
-					let bmp_tok = lexer.next_token();
+					let bmp_tok = lexer.next_token()?;
 					match bmp_tok {
 						Some(parser::Token::Symbol('}')) => {
 							if current_lump.is_some() {
 								let mut lump = current_lump
 									.take()
 									.unwrap();
-								lump.end_chunk = headers
+								let next_chunk = headers
 									.bitmaps_start()
 									+ headers.bitmaps.len()
-										as u32 - 1;
+										as u32;
+								lump.end_chunk = lump_end_chunk(
+									&lexer, &lump, next_chunk,
+								)?;
 								headers.lumps.push(lump);
 							} else {
 								break;
 							}
 						}
-						Some(parser::Token::Ident("Lump")) => {
+						Some(parser::Token::Ident(ref ident))
+							if ident == "Lump" =>
+						{
 							if current_lump.is_some() {
-								panic!("Tried to nest a lump!");
+								return Err(lexer.error(
+									"Tried to nest a lump!"
+										.to_string(),
+								));
 							}
 							current_lump = Some(Lump {
-								name: lexer.get_string_literal(),
+								defined_at_line: lexer.last_line(),
+								section: ChunkSection::Bitmaps,
+								name: lexer.get_string_literal()?,
 								start_chunk: headers
 									.bitmaps_start()
 									+ headers.bitmaps.len()
 										as u32,
 								end_chunk: 0,
 							});
-							lexer.expect_symbol('{');
+							lexer.expect_symbol('{')?;
 						}
 						Some(parser::Token::StringLiteral(bmp_name)) => {
+							check_duplicate_name(
+								&lexer,
+								&mut seen_names,
+								ChunkSection::Bitmaps,
+								&bmp_name,
+							)?;
 							headers.bitmaps.push(bmp_name);
 						}
 						None => {
 							break;
 						}
-						_ => {
-							panic!("Unknown token");
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
 						}
 					}
 				}
 			}
-			Some(parser::Token::Ident("BitmapsMasked")) => {
-				lexer.expect_symbol('{');
+			"BitmapsMasked" => {
+				lexer.expect_symbol('{')?;
 				loop {
-					let bmp_tok = lexer.next_token();
+					let bmp_tok = lexer.next_token()?;
 					match bmp_tok {
 						Some(parser::Token::Symbol('}')) => {
 							if current_lump.is_some() {
 								let mut lump = current_lump
 									.take()
 									.unwrap();
-								lump.end_chunk = headers
+								let next_chunk = headers
 									.bitmaps_masked_start()
 									+ headers
 										.bitmaps_masked
-										.len() as u32 - 1;
+										.len() as u32;
+								lump.end_chunk = lump_end_chunk(
+									&lexer, &lump, next_chunk,
+								)?;
 								headers.lumps.push(lump);
 							} else {
 								break;
 							}
 						}
-						Some(parser::Token::Ident("Lump")) => {
+						Some(parser::Token::Ident(ref ident))
+							if ident == "Lump" =>
+						{
 							if current_lump.is_some() {
-								panic!("Tried to nest a lump!");
+								return Err(lexer.error(
+									"Tried to nest a lump!"
+										.to_string(),
+								));
 							}
 							current_lump = Some(Lump {
-								name: lexer.get_string_literal(),
+								defined_at_line: lexer.last_line(),
+								section:
+									ChunkSection::BitmapsMasked,
+								name: lexer.get_string_literal()?,
 								start_chunk: headers
 									.bitmaps_masked_start()
 									+ headers
@@ -921,111 +2191,166 @@ fn parse_gfx_script(filename: &str) -> std::io::Result<GfxHeaders> {
 										.len() as u32,
 								end_chunk: 0,
 							});
-							lexer.expect_symbol('{');
+							lexer.expect_symbol('{')?;
 						}
 						Some(parser::Token::StringLiteral(bmp_name)) => {
+							check_duplicate_name(
+								&lexer,
+								&mut seen_names,
+								ChunkSection::BitmapsMasked,
+								&bmp_name,
+							)?;
 							headers.bitmaps_masked.push(bmp_name);
 						}
 						None => {
 							break;
 						}
-						_ => {
-							panic!("Unknown token");
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
 						}
 					}
 				}
 			}
-			Some(parser::Token::Ident("Sprites")) => {
-				lexer.expect_symbol('{');
+			"Sprites" => {
+				lexer.expect_symbol('{')?;
 				loop {
-					let sprite_tok = lexer.next_token();
+					let sprite_tok = lexer.next_token()?;
 					match sprite_tok {
 						Some(parser::Token::Symbol('}')) => {
 							if current_lump.is_some() {
 								let mut lump = current_lump
 									.take()
 									.unwrap();
-								lump.end_chunk = headers
+								let next_chunk = headers
 									.sprites_start()
 									+ headers.sprites.len()
-										as u32 - 1;
+										as u32;
+								lump.end_chunk = lump_end_chunk(
+									&lexer, &lump, next_chunk,
+								)?;
 								headers.lumps.push(lump);
 							} else {
 								break;
 							}
 						}
-						Some(parser::Token::Ident("Lump")) => {
+						Some(parser::Token::Ident(ref ident))
+							if ident == "Lump" =>
+						{
 							if current_lump.is_some() {
-								panic!("Tried to nest a lump!");
+								return Err(lexer.error(
+									"Tried to nest a lump!"
+										.to_string(),
+								));
 							}
 							current_lump = Some(Lump {
-								name: lexer.get_string_literal(),
+								defined_at_line: lexer.last_line(),
+								section: ChunkSection::Sprites,
+								name: lexer.get_string_literal()?,
 								start_chunk: headers
 									.sprites_start()
 									+ headers.sprites.len()
 										as u32,
 								end_chunk: 0,
 							});
-							lexer.expect_symbol('{');
+							lexer.expect_symbol('{')?;
 						}
 						Some(parser::Token::StringLiteral(spr_name)) => {
+							check_duplicate_name(
+								&lexer,
+								&mut seen_names,
+								ChunkSection::Sprites,
+								&spr_name,
+							)?;
 							headers.sprites.push(spr_name);
 						}
 						None => {
 							break;
 						}
-						_ => {
-							panic!("Unknown token");
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
 						}
 					}
 				}
 			}
-			Some(parser::Token::Ident("Tiles8")) => {
-				let num_tiles8 = lexer.get_int_literal() as u32;
+			"Tiles8" => {
+				let (num_tiles8, tile8_names) = parse_tile_count(&mut lexer)?;
 				headers.tile8_count = num_tiles8;
+				headers.tile8_names = tile8_names.unwrap_or_default();
 			}
-			Some(parser::Token::Ident("Tiles8Masked")) => {
-				let num_tiles8m = lexer.get_int_literal() as u32;
+			"Tiles8Masked" => {
+				let num_tiles8m = lexer.get_count_literal()?;
 				headers.tile8_masked_count = num_tiles8m;
 			}
-			Some(parser::Token::Ident("Tiles16")) => {
-				let num_tiles16 = lexer.get_int_literal() as u32;
+			"Tiles16" => {
+				let (num_tiles16, tile16_names) = parse_tile_count(&mut lexer)?;
 				headers.tile16_count = num_tiles16;
+				headers.tile16_names = tile16_names;
 			}
-			Some(parser::Token::Ident("Tiles16Masked")) => {
-				let num_tiles16m = lexer.get_int_literal() as u32;
+			"Tiles16Masked" => {
+				let num_tiles16m = lexer.get_count_literal()?;
 				headers.tile16_masked_count = num_tiles16m;
 			}
-			Some(parser::Token::Ident("Tiles32")) => {
-				let num_tiles32 = lexer.get_int_literal() as u32;
+			"Tiles32" => {
+				let (num_tiles32, tile32_names) = parse_tile_count(&mut lexer)?;
 				headers.tile32_count = num_tiles32;
+				headers.tile32_names = tile32_names;
 			}
-			Some(parser::Token::Ident("Tiles32Masked")) => {
-				let num_tiles32m = lexer.get_int_literal() as u32;
+			"Tiles32Masked" => {
+				let num_tiles32m = lexer.get_count_literal()?;
 				headers.tile32_masked_count = num_tiles32m;
 			}
-			Some(parser::Token::Ident("Chunk")) => {
-				let chunk_name = lexer.get_string_literal();
-				headers.misc_chunks.push(MiscChunk::Chunk(chunk_name));
-			}
-			Some(parser::Token::Ident("Article")) => {
-				let chunk_name = lexer.get_string_literal();
-				headers.misc_chunks.push(MiscChunk::Article(chunk_name));
-			}
-			Some(parser::Token::Ident("B8000Text")) => {
-				let chunk_name = lexer.get_string_literal();
-				headers.misc_chunks.push(MiscChunk::B8000Text(chunk_name));
-			}
-			Some(parser::Token::Ident("Terminator")) => {
-				let chunk_name = lexer.get_string_literal();
-				headers.misc_chunks.push(MiscChunk::Terminator(chunk_name));
+			keyword if MISC_CHUNK_KEYWORDS.contains(&keyword) => {
+				let chunk = parse_misc_chunk(&mut lexer, keyword)?;
+				if let Some(name) = chunk.name() {
+					check_duplicate_name(
+						&lexer,
+						&mut seen_names,
+						ChunkSection::Misc,
+						name,
+					)?;
+				}
+				headers.misc_chunks.push(chunk);
 			}
-			Some(parser::Token::Ident("Demo")) => {
-				let demo_number = lexer.get_int_literal() as u32;
-				headers.misc_chunks.push(MiscChunk::Demo(demo_number));
+			"Externs" => {
+				/* A block of misc chunks, just for grouping them in the script. */
+				lexer.expect_symbol('{')?;
+				loop {
+					match lexer.next_token()? {
+						Some(parser::Token::Symbol('}')) | None => {
+							break;
+						}
+						Some(parser::Token::Ident(keyword))
+							if MISC_CHUNK_KEYWORDS
+								.contains(&keyword.as_str()) =>
+						{
+							let chunk = parse_misc_chunk(
+								&mut lexer, &keyword,
+							)?;
+							if let Some(name) = chunk.name() {
+								check_duplicate_name(
+									&lexer,
+									&mut seen_names,
+									ChunkSection::Misc,
+									name,
+								)?;
+							}
+							headers.misc_chunks.push(chunk);
+						}
+						tok => {
+							return Err(
+								lexer.unknown_token(tok.as_ref())
+							);
+						}
+					}
+				}
 			}
-			Some(_) => {
-				panic!("Unknown token");
+			_ => {
+				let tok = parser::Token::Ident(keyword.clone());
+				return Err(lexer.unknown_token(Some(&tok)));
 			}
 		}
 	}
@@ -1033,22 +2358,42 @@ fn parse_gfx_script(filename: &str) -> std::io::Result<GfxHeaders> {
 	Ok(headers)
 }
 
-fn show_usage() {
-	println!("Usage: idgrab <script> [options]");
-	println!("\t--gfxinfo <filename>");
-	println!("\t\tGenerates a GFXINFO(E) file for use with TED or Omnispeak");
-	println!("\t--modid <filename>");
-	println!("\t\tWrites a modid/ugrab compatible .def file.");
-	println!("\t--omnispeak <filename>");
-	println!("\t\tGenerates an omnispeak-compatible GFXCHUNKS variable file");
-	println!("\t--igrab-header <filename>");
-	println!("\t\tCreates a GRAPHEXT/GFXE_EXT C header file.");
-	println!("\t--igrab-asm <filename>");
-	println!("\t\tCreates a GRAPHEXT/GFXE_EXT assembly (.EQU) header.");
-	println!("\t--igrab-version <0.24 | 0.4>");
-	println!("\t\tEmulate the output from a specific IGRAB version.");
-	println!("\t--igrab-underscore-separator");
-	println!("\t\tAdd an underscore before chunk name suffixes (e.g., _SPR)");
+/* Gets the argument after a flag, or shows the usage and exits if it's missing. */
+fn next_flag_argument<'a>(
+	arg_iter: &mut impl Iterator<Item = &'a String>,
+	flag: &str,
+) -> &'a String {
+	arg_iter.next().unwrap_or_else(|| flag_usage_error(flag))
+}
+
+/* Says what a flag's argument should be, shows the usage and exits. */
+fn flag_usage_error(flag: &str) -> ! {
+	eprintln!(
+		"error: {} requires {}",
+		flag,
+		cli::argument_description(flag)
+	);
+	show_usage();
+	std::process::exit(1);
+}
+
+/*
+ * Parses a script for main(), exiting with a message if it has a problem.
+ * Filenames ending in .json are read as the JSON --json writes.
+ */
+fn parse_gfx_script_or_exit(filename: &str, parser_options: parser::ParserOptions) -> GfxHeaders {
+	let is_json = Path::new(filename)
+		.extension()
+		.is_some_and(|ext| ext.eq_ignore_ascii_case("json"));
+	let result = if is_json {
+		json::parse_gfx_json(filename)
+	} else {
+		parse_gfx_script_with_options(filename, parser_options)
+	};
+	result.unwrap_or_else(|error| {
+		eprintln!("Error: {}", error);
+		std::process::exit(1);
+	})
 }
 
 fn main() {
@@ -1057,47 +2402,354 @@ fn main() {
 		show_usage();
 		return;
 	}
+	if args[1] == "--completion" {
+		let shell = args.get(2).map(|s| s.as_str()).unwrap_or("");
+		let stdout = std::io::stdout();
+		if !cli::write_completion(shell, &mut stdout.lock()).unwrap() {
+			eprintln!(
+				"Unsupported shell '{}'. Supported shells are: {}",
+				shell,
+				cli::COMPLETION_SHELLS.join(", ")
+			);
+			std::process::exit(1);
+		}
+		return;
+	}
+	/* These affect how scripts are parsed, so are needed before anything else. */
+	let parser_options = parser::ParserOptions {
+		allow_semicolon_comments: args
+			.iter()
+			.any(|arg| arg == "--allow-semicolon-comments"),
+	};
+	if args[1] == "--diff" {
+		if args.len() < 4 {
+			show_usage();
+			std::process::exit(1);
+		}
+		let mut diff_format = diff::DiffFormat::Text;
+		let mut diff_args = args.iter().skip(4);
+		while let Some(arg) = diff_args.next() {
+			match arg.as_str() {
+				"--diff-format" => {
+					let format_str = next_flag_argument(&mut diff_args, arg);
+					diff_format = diff::DiffFormat::from_name(format_str)
+						.unwrap_or_else(|| flag_usage_error(arg));
+				}
+				"--allow-semicolon-comments" => {}
+				_ => {
+					show_usage();
+					std::process::exit(1);
+				}
+			}
+		}
+		let old_headers = parse_gfx_script_or_exit(&args[2], parser_options);
+		let new_headers = parse_gfx_script_or_exit(&args[3], parser_options);
+		let diffs = old_headers.diff(&new_headers);
+		let stdout = std::io::stdout();
+		diff::write_diff(&mut stdout.lock(), &diffs, diff_format).unwrap();
+		std::process::exit(if diffs.is_empty() { 0 } else { 1 });
+	}
 	let script_filename = &args[1];
-	let headers = parse_gfx_script(script_filename).unwrap();
+	let headers = parse_gfx_script_or_exit(script_filename, parser_options);
+
+	/* Validation needs to know about some flags before we write anything. */
+	let uses_gfxinfoe = args.iter().any(|arg| arg == "--gfxinfo" || arg == "--all");
+	let strict_lumps = args.iter().any(|arg| arg == "--strict-lumps");
+	let check_lumps = strict_lumps || args.iter().any(|arg| arg == "--check-lumps");
+	let enforce_namelen = args.iter().any(|arg| arg == "--igrab-enforce-namelen");
+	let mut has_errors = false;
+	for warning in headers.validate(uses_gfxinfoe, check_lumps) {
+		if warning.is_fatal()
+			|| (strict_lumps && warning.is_lump_problem())
+			|| (enforce_namelen && warning.is_name_length())
+		{
+			eprintln!("Error: {}", warning);
+			has_errors = true;
+		} else {
+			eprintln!("Warning: {}", warning);
+		}
+	}
+	/*
+	 * Names in C and assembly headers must be identifiers, but nothing else
+	 * minds: the stock scripts' names (like 100_PTS1) are fine for omnispeak,
+	 * which adds a prefix.
+	 */
+	let writes_c_headers = args
+		.iter()
+		.any(|arg| arg == "--igrab-header" || arg == "--igrab-asm" || arg == "--all");
+	let allow_invalid_identifiers = args
+		.iter()
+		.any(|arg| arg == "--igrab-allow-invalid-identifiers");
+	if writes_c_headers {
+		if let Err(invalid_names) = headers.check_c_identifiers() {
+			let severity = if allow_invalid_identifiers {
+				"Warning"
+			} else {
+				"Error"
+			};
+			for name in invalid_names {
+				eprintln!(
+					"{}: \"{}\" isn't a valid C identifier, so will break C and assembly headers.",
+					severity, name
+				);
+			}
+			if !allow_invalid_identifiers {
+				eprintln!("Use --igrab-allow-invalid-identifiers to write the headers anyway.");
+				has_errors = true;
+			}
+		}
+	}
+	/* Warn once here, rather than for each header --all writes. */
+	let last_igrab_version = args
+		.iter()
+		.rposition(|arg| arg == "--igrab-version")
+		.and_then(|pos| args.get(pos + 1))
+		.map(|ver| ver.as_str())
+		.unwrap_or("0.4");
+	if writes_c_headers
+		&& last_igrab_version == "0.4"
+		&& args.iter().any(|arg| arg == "--igrab-constexpr")
+	{
+		eprintln!("Warning: --igrab-constexpr has no effect with IGRAB 0.4 (which uses an enum).");
+	}
+	if has_errors {
+		std::process::exit(1);
+	}
 
 	let mut arg_iter = args.iter().skip(2);
 
 	/* We default to 0.4 for igrab output. */
 	let mut igrab_options = IGrabOptions::default();
+	let mut omnispeak_options = OmnispeakOptions::default();
+	let mut modid_options = ModidOptions::default();
+	let mut create_dirs = true;
+
+	let script_basename = Path::new(script_filename)
+		.file_name()
+		.map(|name| name.to_string_lossy().into_owned());
+	omnispeak_options.source_filename = script_basename.clone();
+	modid_options.source_filename = script_basename;
 
 	while let Some(arg) = arg_iter.next() {
 		match arg.as_str() {
+			"--no-create-dirs" => {
+				create_dirs = false;
+			}
+			"--allow-semicolon-comments"
+			| "--check-lumps"
+			| "--strict-lumps"
+			| "--igrab-enforce-namelen"
+			| "--igrab-allow-invalid-identifiers" => {}
 			"--gfxinfo" => {
-				let filename = arg_iter.next().unwrap().as_str();
-				headers.save_gfxinfoe(filename).unwrap();
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_gfxinfoe(filename, create_dirs).unwrap();
+			}
+			"--script" => {
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_script(filename, create_dirs).unwrap();
+			}
+			"--json" => {
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_json(filename, create_dirs).unwrap();
 			}
 			"--modid" => {
-				let filename = arg_iter.next().unwrap().as_str();
-				headers.save_modid_script(filename).unwrap();
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_modid_script(filename, &modid_options, create_dirs)
+					.unwrap();
 			}
 			"--omnispeak" => {
-				let filename = arg_iter.next().unwrap().as_str();
-				headers.save_omnispeak_cfg(filename).unwrap();
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_omnispeak_cfg(
+					filename,
+					&omnispeak_options,
+					create_dirs,
+				)
+				.unwrap();
+			}
+			"--modid-explicit-zero" => {
+				modid_options.explicit_zero_tile8 = true;
+			}
+			"--modid-comments" => {
+				modid_options.emit_comments = true;
+			}
+			"--modid-skip-empty" => {
+				modid_options.skip_empty_sections = true;
+			}
+			"--omnispeak-arrays" => {
+				omnispeak_options.use_arrays_for_sections = true;
+			}
+			"--omnispeak-no-font-vars" => {
+				omnispeak_options.emit_font_vars = false;
+			}
+			"--omnispeak-no-font-section-vars" => {
+				omnispeak_options.emit_font_section_vars = false;
+			}
+			"--omnispeak-no-comment" => {
+				omnispeak_options.emit_generator_comment = false;
+			}
+			"--omnispeak-no-tile-vars" => {
+				omnispeak_options.emit_tile_vars = false;
+			}
+			"--omnispeak-no-section-vars" => {
+				omnispeak_options.emit_section_vars = false;
+				omnispeak_options.emit_font_vars = false;
+				omnispeak_options.emit_font_section_vars = false;
+				omnispeak_options.emit_tile_vars = false;
+			}
+			"--omnispeak-struct-slot-prefix" => {
+				let prefix = next_flag_argument(&mut arg_iter, arg);
+				omnispeak_options.struct_slot_prefix = prefix.clone();
+			}
+			"--omnispeak-demo-aliases" => {
+				omnispeak_options.emit_demo_aliases = true;
+			}
+			"--omnispeak-chunk-prefix" => {
+				omnispeak_options.chunk_prefix =
+					next_flag_argument(&mut arg_iter, arg).clone();
+			}
+			"--omnispeak-b8000text-prefix" => {
+				omnispeak_options.b8000text_prefix =
+					next_flag_argument(&mut arg_iter, arg).clone();
+			}
+			"--omnispeak-article-prefix" => {
+				omnispeak_options.article_prefix =
+					next_flag_argument(&mut arg_iter, arg).clone();
+			}
+			"--omnispeak-terminator-prefix" => {
+				omnispeak_options.terminator_prefix =
+					next_flag_argument(&mut arg_iter, arg).clone();
+			}
+			"--omnispeak-game-ext" => {
+				let enable_str = next_flag_argument(&mut arg_iter, arg).as_str();
+				omnispeak_options.emit_game_ext = match enable_str {
+					"yes" => true,
+					"no" => false,
+					_ => flag_usage_error(arg),
+				};
 			}
 			"--igrab-version" => {
-				let ver_str = arg_iter.next().unwrap().as_str();
+				let ver_str = next_flag_argument(&mut arg_iter, arg).as_str();
 				igrab_options.version = match ver_str {
 					"0.24" => IGrabVersion::ZeroPointTwoFour,
 					"0.4" => IGrabVersion::ZeroPointFour,
-					_ => panic!("Invalid IGRAB version. Only 0.24 and 0.4 are supported!"),
+					_ => flag_usage_error(arg),
+				};
+			}
+			"--timestamps" => {
+				igrab_options.timestamps = true;
+			}
+			"--no-timestamps" => {
+				igrab_options.timestamps = false;
+			}
+			"--igrab-banner" | "--igrab-header-title" => {
+				let banner = next_flag_argument(&mut arg_iter, arg);
+				igrab_options.custom_banner = Some(banner.clone());
+			}
+			"--igrab-extra-define" => {
+				let name = next_flag_argument(&mut arg_iter, arg);
+				let value = next_flag_argument(&mut arg_iter, arg);
+				igrab_options
+					.extra_defines
+					.push((name.clone(), value.clone()));
+			}
+			"--igrab-asm-style" => {
+				let style_str = next_flag_argument(&mut arg_iter, arg).as_str();
+				igrab_options.asm_equals_style = match style_str {
+					"tasm" => AsmEqualsStyle::Tasm,
+					"gas" => AsmEqualsStyle::Gas,
+					_ => flag_usage_error(arg),
 				};
 			}
+			"--igrab-misc-position" => {
+				let position_str = next_flag_argument(&mut arg_iter, arg).as_str();
+				igrab_options.misc_position = match position_str {
+					"in-enum" => MiscPosition::InEnum,
+					"after-amounts" => MiscPosition::AfterAmounts,
+					_ => flag_usage_error(arg),
+				};
+			}
+			"--igrab-annotate-zeros" => {
+				igrab_options.annotate_zero_counts = true;
+			}
+			"--igrab-skip-empty-extern-start" => {
+				igrab_options.skip_empty_extern_start = true;
+			}
+			"--igrab-c-column" => {
+				let column_str = next_flag_argument(&mut arg_iter, arg);
+				igrab_options.c_column = column_str
+					.parse()
+					.unwrap_or_else(|_| flag_usage_error(arg));
+			}
+			"--igrab-enum-column" => {
+				let column_str = next_flag_argument(&mut arg_iter, arg);
+				igrab_options.enum_column = column_str
+					.parse()
+					.unwrap_or_else(|_| flag_usage_error(arg));
+			}
 			"--igrab-underscore-separator" => {
 				igrab_options.append_underscores = true;
 			}
+			"--igrab-include-fonts" => {
+				igrab_options.emit_font_defines = true;
+			}
+			"--igrab-gameext-define" => {
+				igrab_options.emit_gameext_define = true;
+			}
+			"--igrab-no-struct-offsets" => {
+				igrab_options.emit_struct_offsets = false;
+			}
+			"--igrab-no-numexterns" => {
+				igrab_options.emit_numexterns = false;
+			}
+			"--igrab-article-start" => {
+				igrab_options.emit_article_start = true;
+			}
+			"--igrab-b8000text-start" => {
+				igrab_options.emit_b8000text_start = true;
+			}
+			"--igrab-num-demos" => {
+				igrab_options.emit_num_demos = true;
+			}
+			"--igrab-numlumps" => {
+				igrab_options.emit_numlumps = true;
+			}
+			"--igrab-no-thank-you" => {
+				igrab_options.suppress_thank_you = true;
+			}
+			"--igrab-hybrid" => {
+				igrab_options.hybrid_c_asm = true;
+			}
+			"--igrab-pragma-once" => {
+				igrab_options.pragma_once = true;
+			}
+			"--igrab-constexpr" => {
+				igrab_options.use_constexpr = true;
+			}
+			"--all" => {
+				let prefix = next_flag_argument(&mut arg_iter, arg).as_str();
+				let output_options = OutputOptions {
+					create_dirs,
+					igrab: igrab_options.clone(),
+					modid_options: modid_options.clone(),
+					omnispeak_options: omnispeak_options.clone(),
+					..Default::default()
+				};
+				headers.write_all_with_prefix(prefix, &output_options)
+					.unwrap();
+			}
 			"--igrab-header" => {
-				let filename = arg_iter.next().unwrap().as_str();
-				headers.save_igrab_header(filename, &igrab_options).unwrap();
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_igrab_header(filename, &igrab_options, create_dirs)
+					.unwrap();
 			}
 			"--igrab-asm" => {
-				let filename = arg_iter.next().unwrap().as_str();
-				headers.save_igrab_asm_header(filename, &igrab_options)
-					.unwrap();
+				let filename = next_flag_argument(&mut arg_iter, arg).as_str();
+				headers.save_igrab_asm_header(
+					filename,
+					&igrab_options,
+					create_dirs,
+				)
+				.unwrap();
 			}
 			_ => {
 				show_usage();
@@ -1106,3 +2758,1416 @@ fn main() {
 		}
 	}
 }
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+
+	fn omnispeak_cfg_string(
+		headers: &GfxHeaders,
+		omnispeak_options: &OmnispeakOptions,
+	) -> String {
+		let mut out = Vec::new();
+		headers.write_omnispeak_cfg(&mut out, omnispeak_options)
+			.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	fn igrab_asm_header_string(headers: &GfxHeaders, igrab_options: &IGrabOptions) -> String {
+		let mut out = Vec::new();
+		headers.write_igrab_asm_header(&mut out, igrab_options)
+			.unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	fn igrab_header_string(headers: &GfxHeaders, igrab_options: &IGrabOptions) -> String {
+		let mut out = Vec::new();
+		headers.write_igrab_header(&mut out, igrab_options).unwrap();
+		String::from_utf8(out).unwrap()
+	}
+
+	#[test]
+	fn igrab_enum_explicit_values() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLE".to_string(), "CREDITS".to_string()],
+			bitmaps_masked: vec!["STATUS".to_string()],
+			sprites: vec!["KEEN".to_string()],
+			tile16_count: 4,
+			misc_chunks: vec![
+				MiscChunk::Chunk("README".to_string()),
+				MiscChunk::Article("STORY".to_string()),
+			],
+			..Default::default()
+		};
+		let header = igrab_header_string(&headers, &IGrabOptions::default());
+		let explicit: Vec<&str> = header.lines().filter(|l| l.contains(" = ")).collect();
+		/* Only the first bitmap and the first extern (after the tiles) need values. */
+		assert_eq!(explicit, vec!["\t\tTITLEPIC = 3,", "\t\tREADME = 11,"]);
+		assert!(header.contains("\t\tSTATUSPICM,"));
+		assert!(header.contains("\t\tSTORY,"));
+	}
+
+	#[test]
+	fn igrab_enum_single_initializer_across_sections() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps_masked: vec!["STATUS".to_string(), "SCORE".to_string()],
+			sprites: vec!["KEEN".to_string(), "SHOT".to_string()],
+			misc_chunks: vec![MiscChunk::Chunk("README".to_string())],
+			..Default::default()
+		};
+		let header = igrab_header_string(&headers, &IGrabOptions::default());
+		let explicit: Vec<&str> = header.lines().filter(|l| l.contains(" = ")).collect();
+		/* With no tiles in between, every section follows on from the first entry. */
+		assert_eq!(explicit, vec!["\t\tSTATUSPICM = 3,"]);
+		assert!(header.contains("\t\tKEENSPR,"));
+		assert!(header.contains("\t\tREADME,"));
+	}
+
+	#[test]
+	fn igrab_font_defines() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			fonts: vec!["MAINFONT".to_string()],
+			fonts_masked: vec!["FANCYFONT".to_string()],
+			bitmaps: vec!["TITLE".to_string()],
+			..Default::default()
+		};
+		let mut igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(!header.contains("MAINFONT"));
+		assert!(!header.contains("FANCYFONT"));
+
+		igrab_options.emit_font_defines = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define MAINFONT\t\t\t3\n"));
+		assert!(header.contains("#define FANCYFONT\t\t\t4\n"));
+		assert!(header.find("FANCYFONT").unwrap() < header.find("typedef enum").unwrap());
+	}
+
+	#[test]
+	fn omnispeak_section_arrays() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLE".to_string(), "CREDITS".to_string()],
+			misc_chunks: vec![MiscChunk::Demo(0)],
+			lumps: vec![Lump {
+				name: "TITLES".to_string(),
+				start_chunk: 3,
+				end_chunk: 4,
+				defined_at_line: 0,
+				section: ChunkSection::Bitmaps,
+			}],
+			..Default::default()
+		};
+		let omnispeak_options = OmnispeakOptions {
+			use_arrays_for_sections: true,
+			..Default::default()
+		};
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("%int PIC_"));
+		assert!(cfg.contains("%strarray picNames\n\t\"TITLE\",\n\t\"CREDITS\"\n"));
+		assert!(cfg.contains("%intarray picStarts\n\t3,\n\t4\n"));
+		assert!(cfg.contains("%intarray lumpStarts\n\t3\n"));
+		assert!(!cfg.contains("fontNames"));
+
+		/* Names are escaped as in scripts, so they can't end the string early. */
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["SAY\"HI".to_string(), "BACK\\SLASH".to_string()],
+			..Default::default()
+		};
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%strarray picNames\n\t\"SAY\\\"HI\",\n\t\"BACK\\\\SLASH\"\n"));
+	}
+
+	#[test]
+	fn igrab_gameext_define() {
+		let headers = GfxHeaders {
+			extension: Some("CK\"4".to_string()),
+			header_chunk_count: 3,
+			..Default::default()
+		};
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("GAMEEXT"));
+		igrab_options.emit_gameext_define = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("\n#define GAMEEXT \"CK\\\"4\"\n"));
+	}
+
+	#[test]
+	fn igrab_asm_gas_style() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLESCREEN".to_string()],
+			..Default::default()
+		};
+		let igrab_options = IGrabOptions {
+			asm_equals_style: AsmEqualsStyle::Gas,
+			..Default::default()
+		};
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert!(asm.contains("\n.set TITLESCREENPIC, 3\n"));
+		assert!(asm.contains("\n.set NUMFONT, 0\n"));
+		assert!(asm
+			.lines()
+			.all(|l| !l.starts_with(';') && !l.contains('\t')));
+	}
+
+	#[test]
+	fn igrab_custom_banner() {
+		let headers = GfxHeaders {
+			extension: Some("CK4".to_string()),
+			header_chunk_count: 3,
+			..Default::default()
+		};
+		let igrab_options = IGrabOptions {
+			custom_banner: Some("Keen 4 Mod Graphics".to_string()),
+			..Default::default()
+		};
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("//\n// Keen 4 Mod Graphics\n"));
+		assert!(!header.contains("Graphics .H file"));
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert!(asm.contains(";\n; Keen 4 Mod Graphics\n"));
+	}
+
+	#[test]
+	fn igrab_no_struct_offsets() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			..Default::default()
+		};
+		let mut igrab_options = IGrabOptions::default();
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STRUCTPIC "));
+		igrab_options.emit_struct_offsets = false;
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("STRUCT"));
+		assert!(!igrab_asm_header_string(&headers, &igrab_options).contains("STRUCT"));
+	}
+
+	#[test]
+	fn igrab_numexterns() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			misc_chunks: vec![
+				MiscChunk::Chunk("README".to_string()),
+				MiscChunk::Demo(0),
+			],
+			..Default::default()
+		};
+		let mut igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define NUMTILE32M   0\n#define NUMEXTERNS   2\n"));
+		igrab_options.emit_numexterns = false;
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("NUMEXTERNS"));
+	}
+
+	#[test]
+	fn timestamp_format() {
+		assert_eq!(GfxHeaders::format_timestamp(0), "1970-01-01 00:00:00 UTC");
+		assert_eq!(
+			GfxHeaders::format_timestamp(951827696),
+			"2000-02-29 12:34:56 UTC"
+		);
+		assert_eq!(
+			GfxHeaders::format_timestamp(1790000000),
+			"2026-09-21 14:13:20 UTC"
+		);
+	}
+
+	#[test]
+	fn output_creates_parent_dirs() {
+		let base_dir =
+			std::env::temp_dir().join(format!("idgrab-test-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&base_dir);
+		let out_path = base_dir.join("tmp").join("subdir").join("out.h");
+
+		assert!(create_output_file(&out_path, false).is_err());
+		create_output_file(&out_path, true).unwrap();
+		assert!(out_path.is_file());
+
+		std::fs::remove_dir_all(&base_dir).unwrap();
+	}
+
+	#[test]
+	fn write_all_with_prefix_filenames() {
+		let mut headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLE".to_string()],
+			misc_chunks: vec![MiscChunk::Demo(0)],
+			..Default::default()
+		};
+		let out_dir =
+			std::env::temp_dir().join(format!("idgrab-prefix-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&out_dir);
+		let opts = OutputOptions {
+			output_dir: Some(out_dir.clone()),
+			..Default::default()
+		};
+		let list_files = || {
+			let mut files: Vec<String> = std::fs::read_dir(out_dir.join("mod"))
+				.unwrap()
+				.map(|entry| {
+					entry.unwrap().file_name().to_string_lossy().into_owned()
+				})
+				.collect();
+			files.sort();
+			files
+		};
+
+		/* Without an Extension, the igrab headers fall back to GFXTMP. */
+		headers.write_all_with_prefix("mod/ck4", &opts).unwrap();
+		assert_eq!(
+			list_files(),
+			vec![
+				"GFXTMP.EQU",
+				"GFXTMP.H",
+				"ck4.def",
+				"ck4.gfx",
+				"ck4_gfx.txt"
+			]
+		);
+
+		std::fs::remove_dir_all(&out_dir).unwrap();
+		headers.extension = Some("CK4".to_string());
+		headers.write_all_with_prefix("mod/ck4", &opts).unwrap();
+		assert_eq!(
+			list_files(),
+			vec![
+				"GFXCK4.EQU",
+				"GFXCK4.H",
+				"ck4.def",
+				"ck4.gfx",
+				"ck4_gfx.txt"
+			]
+		);
+		std::fs::remove_dir_all(&out_dir).unwrap();
+	}
+
+	#[test]
+	fn write_all_formats_filenames() {
+		let headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLE".to_string()],
+			misc_chunks: vec![MiscChunk::Demo(0)],
+			..Default::default()
+		};
+		let out_dir =
+			std::env::temp_dir().join(format!("idgrab-all-{}", std::process::id()));
+		let _ = std::fs::remove_dir_all(&out_dir);
+		let opts = OutputOptions {
+			modid: false,
+			output_dir: Some(out_dir.clone()),
+			..Default::default()
+		};
+		headers.write_all_formats("CK4", &opts).unwrap();
+
+		let mut files: Vec<String> = std::fs::read_dir(&out_dir)
+			.unwrap()
+			.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+			.collect();
+		files.sort();
+		assert_eq!(
+			files,
+			vec![
+				"GFXCHUNKS_CK4.CFG",
+				"GFXE_CK4.EQU",
+				"GFXE_CK4.H",
+				"GFXINFO.E"
+			]
+		);
+		std::fs::remove_dir_all(&out_dir).unwrap();
+	}
+
+	#[test]
+	fn script_extension_after_sections() {
+		let before = parse_gfx_script_str(
+			"Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\nTiles16 4\n",
+		)
+		.unwrap();
+		let after = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nTiles16 4\nExtension \"CK4\"\n",
+		)
+		.unwrap();
+		assert_eq!(before, after);
+
+		let mut out = Vec::new();
+		after.write_script(&mut out).unwrap();
+		assert!(String::from_utf8(out)
+			.unwrap()
+			.starts_with("Extension \"CK4\"\n"));
+	}
+
+	#[test]
+	fn script_round_trip() {
+		for script in [
+			"scripts/keen4.idgrab",
+			"scripts/keen5.idgrab",
+			"scripts/keen6.idgrab",
+		]
+		.iter()
+		{
+			let headers = parse_gfx_script(script).unwrap();
+			let mut out = Vec::new();
+			headers.write_script(&mut out).unwrap();
+			let reparsed =
+				parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap();
+			assert_eq!(headers, reparsed, "{} didn't round-trip", script);
+		}
+	}
+
+	#[test]
+	fn named_tile16s() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nTiles16 {\n\t\"GRASS\"\n\t\"\"\n\t\"STONE\"\n}\nChunk \"README\"\nDemo 0\n",
+		).unwrap();
+		assert_eq!(headers.tile16_count, 3);
+		assert_eq!(
+			parse_gfx_script_str("Tiles16 4\n").unwrap().tile16_names,
+			None
+		);
+
+		let mut igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		/* GRASS follows on from TITLEPIC, but the unnamed tile leaves a gap before STONE. */
+		assert!(header.contains("\t\tGRASS_TILE16,"));
+		assert!(header.contains("\t\tSTONE_TILE16 = 6,\n\t\tREADME,"));
+
+		igrab_options.version = IGrabVersion::ZeroPointTwoFour;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define GRASS_TILE16\t\t4\n"));
+		assert!(header.contains("#define STONE_TILE16\t\t6\n"));
+
+		let asm = igrab_asm_header_string(&headers, &IGrabOptions::default());
+		assert!(asm.contains("GRASS_TILE16\t\t\t\t=\t4\n"));
+
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg.contains("%int TILE16_GRASS 4\n%int TILE16_STONE 6\n"));
+
+		let mut out = Vec::new();
+		headers.write_script(&mut out).unwrap();
+		assert_eq!(
+			parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap(),
+			headers
+		);
+	}
+
+	#[test]
+	fn named_tile8s() {
+		let headers = parse_gfx_script_str(
+			"Tiles8 {\n\t\"GRASS\"\n\t\"STONE\"\n}\nTiles16 4\nDemo 0\n",
+		)
+		.unwrap();
+		assert_eq!(headers.tile8_count, 2);
+
+		let header = igrab_header_string(&headers, &IGrabOptions::default());
+		assert!(header.contains("#define GRASS_TILE8_OFFSET\t0\n"));
+		assert!(header.contains("#define STONE_TILE8_OFFSET\t1\n"));
+
+		let asm = igrab_asm_header_string(&headers, &IGrabOptions::default());
+		assert!(asm.contains("STONE_TILE8_OFFSET  \t=\t1\n"));
+
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg.contains("%int TILE8_GRASS 0\n%int TILE8_STONE 1\n"));
+
+		let mut out = Vec::new();
+		headers.write_script(&mut out).unwrap();
+		assert_eq!(
+			parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap(),
+			headers
+		);
+	}
+
+	#[test]
+	fn game_variant_defaults() {
+		let headers = parse_gfx_script_str("Game Keen5\n").unwrap();
+		assert_eq!(headers.game, GameVariant::Keen5);
+		assert_eq!(headers.extension, Some("CK5".to_string()));
+		assert_eq!(headers.header_chunk_count, 3);
+
+		/* Explicit keywords win, whichever order they're in. */
+		let before = parse_gfx_script_str("Extension \"KDR\"\nGame \"Keen4\"\n").unwrap();
+		let after = parse_gfx_script_str("Game Keen4\nExtension \"KDR\"\n").unwrap();
+		assert_eq!(before.extension, Some("KDR".to_string()));
+		assert_eq!(before, after);
+
+		let mut out = Vec::new();
+		after.write_script(&mut out).unwrap();
+		assert_eq!(
+			parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap(),
+			after
+		);
+	}
+
+	#[test]
+	fn omnispeak_game_ext() {
+		let mut headers = parse_gfx_script_str("Extension \"CK4\"\nDemo 0\n").unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.starts_with(&format!(
+			"# GFX Header generated by idGrab {}\n\n%string gameExt \"CK4\"\n",
+			IDGRAB_VERSION.unwrap()
+		)));
+
+		omnispeak_options.emit_game_ext = false;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("gameExt"));
+
+		omnispeak_options.emit_game_ext = true;
+		headers.extension = Some("C\"K\\4".to_string());
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%string gameExt \"C\\\"K\\\\4\"\n"));
+
+		headers.extension = None;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("gameExt"));
+	}
+
+	#[test]
+	fn modid_skip_empty_sections() {
+		let headers =
+			parse_gfx_script_str("Fonts {}\nBitmaps {\n\t\"TITLE\"\n}\nTiles16 4\n")
+				.unwrap();
+		let modid_string = |modid_options: &ModidOptions| {
+			let mut out = Vec::new();
+			headers.write_modid_script(&mut out, modid_options).unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		let mut modid_options = ModidOptions::default();
+		assert!(modid_string(&modid_options).contains("\t\tFONT\t\t0 3\n"));
+
+		modid_options.skip_empty_sections = true;
+		let script = modid_string(&modid_options);
+		assert!(!script.contains("FONT"));
+		assert!(script.contains("\t\tPICS\t\t1 3 0\n"));
+		assert!(script.contains("\t\tTILE16\t\t4 4\n"));
+	}
+
+	#[test]
+	fn igrab_hybrid_header() {
+		let headers = parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\n")
+			.unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("/*"));
+
+		igrab_options.hybrid_c_asm = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		let asm_start = header.find("\n/*\n").unwrap();
+		let asm_end = header.find("*/\n").unwrap();
+		assert!(header.find("#define STARTEXTERNS").unwrap() < asm_start);
+		let asm = &header[asm_start..asm_end];
+		assert!(asm.contains("TITLEPIC\t\t\t\t=\t3\n"));
+		assert!(asm.contains("STARTEXTERNS  \t=\t4\n"));
+		assert!(header[asm_end..].contains("Thank you for using idGrab!"));
+	}
+
+	#[test]
+	fn script_crlf_line_endings() {
+		let lf_script = std::fs::read_to_string("scripts/keen4.idgrab").unwrap();
+		let crlf_script = lf_script.replace('\n', "\r\n");
+		assert_eq!(
+			parse_gfx_script_str(&crlf_script).unwrap(),
+			parse_gfx_script_str(&lf_script).unwrap()
+		);
+	}
+
+	#[test]
+	fn igrab_start_defines_aligned() {
+		let headers =
+			parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\nTiles16 4\n").unwrap();
+		let header = igrab_header_string(&headers, &IGrabOptions::default());
+		let value_columns: Vec<usize> = header
+			.lines()
+			.filter(|l| l.starts_with("#define START"))
+			.map(|l| l.rfind(' ').unwrap())
+			.collect();
+		assert_eq!(value_columns.len(), 12);
+		assert!(value_columns
+			.iter()
+			.all(|&col| col == "#define STARTSPRITES".len()));
+	}
+
+	/*
+	 * As for the .EQU, testdata/GFXE_CK4.H was written by idGrab, not IGRAB.
+	 * Whether IGRAB 0.4 really writes the enum this way is unverified.
+	 */
+	#[test]
+	fn igrab_header_snapshot() {
+		let headers = parse_gfx_script("scripts/keen4.idgrab").unwrap();
+		let igrab_options = IGrabOptions {
+			timestamps: false,
+			..Default::default()
+		};
+		let header = igrab_header_string(&headers, &igrab_options);
+		/* Only the first entry of the enum has a value; the rest have a comment. */
+		assert!(header.contains("typedef enum {\n\t\tHELPMENUPIC = 6,\n\t\tH_LARROWPIC,"));
+		assert_eq!(header, include_str!("../testdata/GFXE_CK4.H"));
+	}
+
+	/*
+	 * testdata/GFXE_CK4.EQU was written by idGrab, not IGRAB, so this only
+	 * catches changes to the output, not differences from IGRAB.
+	 */
+	#[test]
+	fn igrab_asm_header_snapshot() {
+		let headers = parse_gfx_script("scripts/keen4.idgrab").unwrap();
+		let igrab_options = IGrabOptions {
+			timestamps: false,
+			..Default::default()
+		};
+		assert_eq!(
+			igrab_asm_header_string(&headers, &igrab_options),
+			include_str!("../testdata/GFXE_CK4.EQU")
+		);
+	}
+
+	#[test]
+	fn omnispeak_no_lumps() {
+		let headers = parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\nDemo 0\n").unwrap();
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg.ends_with("%int NUMLUMPS 0\n"));
+		assert!(!cfg.contains("%intarray lumpStarts"));
+		assert!(!cfg.contains("# Lump names"));
+	}
+
+	#[test]
+	fn igrab_no_thank_you() {
+		let headers =
+			parse_gfx_script_str("Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\n")
+				.unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(igrab_header_string(&headers, &igrab_options).contains("Thank you"));
+
+		igrab_options.suppress_thank_you = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(!header.contains("Thank you"));
+		assert!(header.contains("// Graphics .H file for CK4"));
+		assert!(header.ends_with("#define STARTEXTERNS 4\n"));
+		assert!(!igrab_asm_header_string(&headers, &igrab_options).contains("Thank you"));
+	}
+
+	#[test]
+	fn script_externs_block() {
+		let top_level =
+			parse_gfx_script_str("Chunk \"FOO\"\nArticle \"BAR\"\nDemo 0\n").unwrap();
+		let block = parse_gfx_script_str(
+			"Externs {\n\tChunk \"FOO\"\n\tArticle \"BAR\"\n}\nDemo 0\n",
+		)
+		.unwrap();
+		assert_eq!(top_level.misc_chunks.len(), 3);
+		assert_eq!(block.misc_chunks, top_level.misc_chunks);
+	}
+
+	#[test]
+	fn igrab_numlumps() {
+		let headers =
+			parse_gfx_script_str("Bitmaps {\nLump \"HELP\" {\n\t\"TITLE\"\n}\n}\n")
+				.unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("NUMLUMPS"));
+		assert!(!igrab_asm_header_string(&headers, &igrab_options).contains("NUMLUMPS"));
+
+		igrab_options.emit_numlumps = true;
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("\n#define NUMLUMPS 1\n"));
+		assert!(igrab_asm_header_string(&headers, &igrab_options)
+			.contains("\nNUMLUMPS  \t=\t1\n"));
+	}
+
+	#[test]
+	fn script_error_filename() {
+		let error = parse_gfx_lexer(
+			parser::Lexer::from_str("Extension \"CK4\"\nBogus\n")
+				.with_filename(Path::new("scripts/bad.idgrab")),
+		)
+		.unwrap_err();
+		assert_eq!(error.to_string(), "scripts/bad.idgrab:2: Unknown token!");
+		assert_eq!(error.line, 2);
+		assert_eq!(error.found.as_deref(), Some("Ident(\"Bogus\")"));
+	}
+
+	#[test]
+	fn script_errors() {
+		let error_string =
+			|script: &str| parse_gfx_script_str(script).unwrap_err().to_string();
+		assert_eq!(
+			error_string("Bitmaps {\n\tLump \"A\" {\n\tLump \"B\" {\n"),
+			"line 3: Tried to nest a lump!"
+		);
+		assert_eq!(error_string("Game Keen7\n"), "line 1: Unknown game Keen7!");
+		assert_eq!(
+			error_string("Extension"),
+			"line 1: Expected string literal, but got EOF!"
+		);
+		assert_eq!(
+			error_string("Chunk \"README\"\nTiles16 {\n\t7\n}\n"),
+			"line 3: Unknown token!"
+		);
+
+		let error = parse_gfx_script("scripts/missing.idgrab").unwrap_err();
+		assert_eq!(error.line, 0);
+		assert!(error.to_string().starts_with("scripts/missing.idgrab: "));
+	}
+
+	#[test]
+	fn script_duplicate_names() {
+		let error = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n\tLump \"L\" {\n\t\t\"TITLE\"\n\t}\n}\n",
+		)
+		.unwrap_err();
+		assert_eq!(error.line, 4);
+		assert_eq!(
+			error.message,
+			"Duplicate name \"TITLE\" in the bitmaps (first used on line 2)!"
+		);
+		/* Misc chunks share one namespace, whatever their type. */
+		assert_eq!(
+			parse_gfx_script_str("Chunk \"README\"\nExterns {\n\tArticle \"README\"\n}\n")
+				.unwrap_err()
+				.to_string(),
+			"line 3: Duplicate name \"README\" in the misc chunks (first used on line 1)!"
+		);
+
+		/* The same name in different sections gets a different suffix, so is fine. */
+		let headers = parse_gfx_script_str(
+			"Fonts {\n\t\"TITLE\"\n}\nBitmaps {\n\t\"TITLE\"\n}\n\
+			 BitmapsMasked {\n\t\"TITLE\"\n}\nSprites {\n\t\"TITLE\"\n}\n\
+			 Chunk \"TITLE\"\nDemo 0\nDemo 0\n",
+		)
+		.unwrap();
+		let header = igrab_header_string(&headers, &IGrabOptions::default());
+		assert!(header.contains("TITLEPIC = 4,"));
+		assert!(header.contains("TITLEPICM,"));
+		assert!(header.contains("TITLESPR,"));
+	}
+
+	#[test]
+	fn omnispeak_tile_vars() {
+		let headers = parse_gfx_script_str(
+			"Sprites {\n\t\"KEEN\"\n}\nTiles8 104\nTiles16 4\nDemo 0\n",
+		)
+		.unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int STARTSPRITES 3\n# Tiles\n%int NUMTILE8 104\n"));
+		assert!(cfg.contains("%int NUMTILE16 4\n"));
+		assert!(cfg.contains("%int STARTTILE8 4\n%int STARTTILE8M 5\n%int STARTTILE16 5\n"));
+
+		omnispeak_options.emit_tile_vars = false;
+		assert!(!omnispeak_cfg_string(&headers, &omnispeak_options).contains("TILE"));
+	}
+
+	#[test]
+	fn omnispeak_all_tile_vars() {
+		let headers = parse_gfx_script_str(
+			"Tiles8 104\nTiles8Masked 20\nTiles16 4\nTiles16Masked 5\nTiles32 2\nTiles32Masked 1\nDemo 0\n",
+		).unwrap();
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg.contains(concat!(
+			"# Tiles\n",
+			"%int NUMTILE8 104\n",
+			"%int NUMTILE8M 20\n",
+			"%int NUMTILE16 4\n",
+			"%int NUMTILE16M 5\n",
+			"%int NUMTILE32 2\n",
+			"%int NUMTILE32M 1\n",
+			"%int STARTTILE8 3\n",
+			"%int STARTTILE8M 4\n",
+			"%int STARTTILE16 5\n",
+			"%int STARTTILE16M 9\n",
+			"%int STARTTILE32 14\n",
+			"%int STARTTILE32M 16\n",
+		)));
+	}
+
+	#[test]
+	fn omnispeak_font_vars() {
+		let headers = parse_gfx_script_str(
+			"Fonts {\n\t\"MAIN\"\n}\nFontsMasked {\n\t\"FANCY\"\n}\nDemo 0\n",
+		)
+		.unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains(
+			"%int FONM_FANCY 4\n%int NUMFONT 1\n%int NUMFONTM 1\n%int STARTFONT 3\n%int STARTFONTM 4\n"
+		));
+
+		assert!(cfg.contains(
+			"%int NUMFONTS 1\n%int FONTS_START 3\n%int NUMFONTSM 1\n%int FONTSM_START 4\n"
+		));
+
+		omnispeak_options.emit_font_vars = false;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("NUMFONT "));
+		assert!(!cfg.contains("STARTFONT"));
+		assert!(cfg.contains("%int NUMFONTS 1\n"));
+
+		omnispeak_options.emit_font_section_vars = false;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("NUMFONT"));
+		assert!(!cfg.contains("FONTS_START"));
+		assert!(!cfg.contains("FONTSM_START"));
+	}
+
+	#[test]
+	fn igrab_num_demos() {
+		let headers =
+			parse_gfx_script_str("Chunk \"README\"\nDemo 0\nDemo 1\nDemo 2\n").unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("NUMDEMOSAVES"));
+
+		igrab_options.emit_num_demos = true;
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define NUMDEMOSAVES 3\n"));
+		assert!(igrab_asm_header_string(&headers, &igrab_options)
+			.contains("NUMDEMOSAVES  \t=\t3\n"));
+
+		let headers = parse_gfx_script_str("Chunk \"README\"\n").unwrap();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("NUMDEMOSAVES"));
+	}
+
+	#[test]
+	fn modid_misc_chunks_without_tile8() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nTiles8Masked 20\nTiles16 4\nChunk \"README\"\nDemo 0\nDemo 1\n",
+		).unwrap();
+		/* 3 header chunks, 1 bitmap, 1 tile8m chunk (but no tile8 chunk) and 4 tile16s. */
+		assert_eq!(headers.misc_start(), 9);
+		let modid_string = |modid_options: &ModidOptions| {
+			let mut out = Vec::new();
+			headers.write_modid_script(&mut out, modid_options).unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		let mut modid_options = ModidOptions::default();
+		let script = modid_string(&modid_options);
+		assert!(script
+			.contains("\t\tSPRITES\t\t0 4 2\n\t\tTILE8M\t\t20 4\n\t\tTILE16\t\t4 5\n"));
+		assert!(script.ends_with("\t\tMISC 9 README\n\t\tDEMO 10 0\n\t\tDEMO 11 1\n"));
+
+		modid_options.explicit_zero_tile8 = true;
+		assert!(modid_string(&modid_options)
+			.contains("\t\tTILE8\t\t0 4\n\t\tTILE8M\t\t20 4\n\t\tTILE16\t\t4 5\n"));
+	}
+
+	#[test]
+	fn gfxinfoe_empty_sections() {
+		let headers =
+			parse_gfx_script_str("Tiles8 104\nTiles16 4\nChunk \"README\"\n").unwrap();
+		let mut out = Vec::new();
+		headers.write_gfxinfoe(&mut out).unwrap();
+		let words: Vec<u16> = out
+			.chunks(2)
+			.map(|pair| u16::from(pair[0]) | u16::from(pair[1]) << 8)
+			.collect();
+		assert_eq!(
+			words,
+			vec![
+				104, 0, 4, 0, 0, 0, // Tile counts
+				3, 4, 4, 8, 8, 8, // Tile starts
+				0, 0, 0, // Bitmap, masked bitmap and sprite counts
+				3, 3, 3, // ...which all start where the (empty) fonts end
+				0, 1, 2, // Header chunks
+				1, 8, // Misc chunks
+			]
+		);
+	}
+
+	#[test]
+	fn igrab_b8000text_start() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\nB8000Text \"ORDERSCREEN\"\nB8000Text \"OUTOFMEM\"\n",
+		).unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("STARTB800TEXT"));
+
+		igrab_options.emit_b8000text_start = true;
+		/* 3 header chunks, 1 bitmap, then README. */
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STARTB800TEXT 5\n"));
+		assert!(igrab_asm_header_string(&headers, &igrab_options)
+			.contains("STARTB800TEXT  \t=\t5\n"));
+		/* It lines up with the other START* defines. */
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STARTEXTERNS  4\n#define STARTB800TEXT 5\n"));
+
+		let headers = parse_gfx_script_str("Chunk \"README\"\n").unwrap();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("STARTB800TEXT"));
+	}
+
+	#[test]
+	fn igrab_article_start() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\nArticle \"HELPMENU\"\nArticle \"STORY\"\n",
+		).unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("STARTARTICLE"));
+
+		igrab_options.emit_article_start = true;
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STARTEXTERNS 4\n#define STARTARTICLE 5\n"));
+		assert!(igrab_asm_header_string(&headers, &igrab_options)
+			.contains("STARTARTICLE  \t=\t5\n"));
+	}
+
+	#[test]
+	fn omnispeak_struct_chunks() {
+		let mut headers = parse_gfx_script_str("Demo 0\n").unwrap();
+		let mut omnispeak_options = OmnispeakOptions {
+			emit_generator_comment: false,
+			..Default::default()
+		};
+		assert!(
+			omnispeak_cfg_string(&headers, &omnispeak_options).starts_with(
+				"%int STRUCTPIC 0\n%int STRUCTPICM 1\n%int STRUCTSPRITE 2\n\n"
+			)
+		);
+
+		headers.header_chunk_count = 2;
+		omnispeak_options.struct_slot_prefix = "HEADER".to_string();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.starts_with("%int HEADER0 0\n%int HEADER1 1\n\n"));
+		assert!(!cfg.contains("STRUCTPIC"));
+	}
+
+	#[test]
+	fn igrab_large_chunk_ids() {
+		/* Chunk numbers past i16::MAX mustn't come out negative or truncated. */
+		let headers = GfxHeaders {
+			header_chunk_count: 65533,
+			bitmaps: vec!["TITLE".to_string(), "CREDITS".to_string()],
+			misc_chunks: vec![MiscChunk::Chunk("README".to_string())],
+			..Default::default()
+		};
+		let mut igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("\t\tTITLEPIC = 65533,"));
+		assert!(header.contains("\t\tCREDITSPIC,"));
+		assert!(header.contains("// 65534"));
+		assert!(header.contains("\t\tREADME,                              // 65535\n"));
+		assert!(igrab_asm_header_string(&headers, &igrab_options)
+			.contains("CREDITSPIC\t\t\t\t=\t65534\n"));
+
+		igrab_options.version = IGrabVersion::ZeroPointTwoFour;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define CREDITSPIC\t\t65534\n"));
+		assert!(header.contains("#define STARTEXTERNS 65535\n"));
+	}
+
+	#[test]
+	fn omnispeak_misc_prefixes() {
+		let headers = parse_gfx_script_str(
+			"Chunk \"README\"\nB8000Text \"ORDERSCREEN\"\nArticle \"STORY\"\nTerminator \"T_DEMO\"\nDemo 0\n",
+		).unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains(
+			"%int EXTERN_README 3\n%int EXTERN_ORDERSCREEN 4\n%int TEXT_STORY 5\n%int EXTERN_T_DEMO 6\n"
+		));
+
+		omnispeak_options.chunk_prefix = "CHUNK_".to_string();
+		omnispeak_options.b8000text_prefix = "B800_".to_string();
+		omnispeak_options.article_prefix = "ARTICLE_".to_string();
+		omnispeak_options.terminator_prefix = "TERM_".to_string();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains(
+			"%int CHUNK_README 3\n%int B800_ORDERSCREEN 4\n%int ARTICLE_STORY 5\n%int TERM_T_DEMO 6\n"
+		));
+	}
+
+	#[test]
+	fn omnispeak_demo_aliases() {
+		let headers =
+			parse_gfx_script_str("Chunk \"README\"\nDemo 0\nDemo 1\nDemo 2\n").unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int DEMOSTART 4\n"));
+		assert!(!cfg.contains("STARTDEMO"));
+		assert!(!cfg.contains("NUMDEMOS"));
+
+		omnispeak_options.emit_demo_aliases = true;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int DEMOSTART 4\n%int STARTDEMO 4\n%int NUMDEMOS 3\n"));
+	}
+
+	#[test]
+	fn omnispeak_without_demos() {
+		let headers = parse_gfx_script_str(
+			"Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\n",
+		)
+		.unwrap();
+		let omnispeak_options = OmnispeakOptions {
+			emit_demo_aliases: true,
+			..Default::default()
+		};
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int EXTERN_README 4\n"));
+		assert!(!cfg.contains("DEMO"));
+	}
+
+	#[test]
+	fn igrab_misc_after_amounts() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\nDemo 0\n",
+		)
+		.unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(igrab_header_string(&headers, &igrab_options).contains("\t\tREADME,"));
+
+		igrab_options.misc_position = MiscPosition::AfterAmounts;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(!header.contains("\t\tREADME"));
+		assert!(!header.contains("\t\tDEMO0"));
+		let misc = header.find("// Misc chunks (externs)").unwrap();
+		assert!(header.find("#define NUMTILE32M").unwrap() < misc);
+		assert!(header[misc..].contains("#define README\t\t\t4\n#define DEMO0\t\t\t5\n"));
+	}
+
+	#[test]
+	fn omnispeak_lumps_in_header_chunks() {
+		let mut headers = parse_gfx_script_str(
+			"Bitmaps {\nLump \"STRUCTS\" {\n\t\"TITLE\"\n}\n}\nDemo 0\n",
+		)
+		.unwrap();
+		headers.lumps[0].start_chunk = 0;
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int STRUCTPIC 0\n"));
+		assert!(cfg.contains("%intarray lumpStarts\n\t@STRUCTPIC\n"));
+		assert!(cfg.contains("%intarray lumpEnds\n\t@PIC_TITLE\n"));
+
+		headers.header_chunk_count = 4;
+		headers.lumps[0].start_chunk = 1;
+		omnispeak_options.struct_slot_prefix = "HEADER".to_string();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int HEADER1 1\n"));
+		assert!(cfg.contains("%intarray lumpStarts\n\t@HEADER1\n"));
+	}
+
+	#[test]
+	fn named_tile32s() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nTiles16 2\nTiles32 {\n\t\"CASTLE\"\n\t\"\"\n\t\"TOWER\"\n}\nDemo 0\n",
+		).unwrap();
+		assert_eq!(headers.tile32_count, 3);
+		assert_eq!(
+			parse_gfx_script_str("Tiles32 4\n").unwrap().tile32_names,
+			None
+		);
+
+		/* 3 header chunks, 1 bitmap and 2 tile16s come first. */
+		let mut igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("\t\tCASTLE_TILE32 = 6,"));
+		assert!(header.contains("\t\tTOWER_TILE32 = 8,"));
+
+		igrab_options.version = IGrabVersion::ZeroPointTwoFour;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define CASTLE_TILE32\t\t6\n"));
+		assert!(header.contains("#define TOWER_TILE32\t\t8\n"));
+
+		let asm = igrab_asm_header_string(&headers, &IGrabOptions::default());
+		assert!(asm.contains("TOWER_TILE32\t\t\t\t=\t8\n"));
+
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg
+			.contains("# Named Tiles32\n%int TILE32_CASTLE 6\n%int TILE32_TOWER 8\n"));
+
+		let mut out = Vec::new();
+		headers.write_script(&mut out).unwrap();
+		assert_eq!(
+			parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap(),
+			headers
+		);
+	}
+
+	#[test]
+	fn igrab_annotate_zero_counts() {
+		let headers =
+			parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\nTiles16 4\n").unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("(none defined)"));
+
+		igrab_options.annotate_zero_counts = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define NUMPICS      1\n"));
+		assert!(header.contains(
+			"#define NUMTILE16    4\n#define NUMTILE16M   0 // (none defined)\n"
+		));
+		assert!(header.contains("#define NUMEXTERNS   0 // (none defined)\n"));
+	}
+
+	#[test]
+	fn script_negative_demo() {
+		assert_eq!(
+			parse_gfx_script_str("Demo -1\n").unwrap_err().to_string(),
+			"line 1: Expected a non-negative integer, but got -1!\n\tDemo -1"
+		);
+	}
+
+	#[test]
+	fn script_negative_tile_count() {
+		let error = parse_gfx_script_str("Tiles8 104\nTiles16Masked -4\n").unwrap_err();
+		assert_eq!(error.line, 2);
+		assert!(error
+			.to_string()
+			.starts_with("line 2: Expected a non-negative integer, but got -4!"));
+	}
+
+	#[test]
+	fn modid_grstarts() {
+		let mut headers = parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\n").unwrap();
+		let modid_string = |headers: &GfxHeaders| {
+			let mut out = Vec::new();
+			headers.write_modid_script(&mut out, &ModidOptions::default())
+				.unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		assert!(modid_string(&headers).contains("\tGRSTARTS 3\n"));
+
+		headers.header_chunk_count = 4;
+		let script = modid_string(&headers);
+		assert!(script.contains("\tGRSTARTS 4\n"));
+		assert!(script.contains("\t\tPICS\t\t1 4 0\n"));
+	}
+
+	#[test]
+	fn igrab_skip_empty_extern_start() {
+		let headers = parse_gfx_script_str("Tiles8 104\nTiles16 4\n").unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STARTEXTERNS 8\n"));
+
+		igrab_options.skip_empty_extern_start = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("#define STARTTILE32M 8\n"));
+		assert!(!header.contains("STARTEXTERNS"));
+		assert!(!igrab_asm_header_string(&headers, &igrab_options).contains("STARTEXTERNS"));
+
+		let headers = parse_gfx_script_str("Tiles16 4\nChunk \"README\"\n").unwrap();
+		assert!(igrab_header_string(&headers, &igrab_options)
+			.contains("#define STARTEXTERNS 7\n"));
+	}
+
+	#[test]
+	fn modid_comments() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\tLump \"TITLES\" {\n\t\t\"TITLE\"\n\t\t\"CREDITS\"\n\t}\n}\nChunk \"README\"\n",
+		).unwrap();
+		let modid_string = |modid_options: &ModidOptions| {
+			let mut out = Vec::new();
+			headers.write_modid_script(&mut out, modid_options).unwrap();
+			String::from_utf8(out).unwrap()
+		};
+		let mut modid_options = ModidOptions {
+			emit_version_comment: false,
+			..Default::default()
+		};
+		assert_eq!(modid_string(&modid_options).matches('#').count(), 1);
+
+		modid_options.emit_comments = true;
+		let script = modid_string(&modid_options);
+		assert!(script.contains("\t\t# Bitmaps start here\n\t\tPICS\t\t2 3 0\n"));
+		assert!(script.contains("\t\t# Masked tile32s start here\n"));
+		assert!(script.ends_with(
+			"\t\t# Lump: TITLES (chunks 3-4)\n\t\t# Misc chunks start here\n\t\tMISC 5 README\n"
+		));
+	}
+
+	#[test]
+	fn igrab_asm_empty_sections() {
+		let igrab_options = IGrabOptions {
+			timestamps: false,
+			..Default::default()
+		};
+		let headers = parse_gfx_script_str("Extension \"CK4\"\n").unwrap();
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert!(asm.contains("=====\n\n;\n; Amount of each data item\n"));
+
+		/* Empty sections don't leave blank lines between the others. */
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nSprites {\n\t\"KEEN\"\n}\n",
+		)
+		.unwrap();
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert!(asm.contains("TITLEPIC\t\t\t\t=\t3\n\nKEENSPR\t\t\t\t\t=\t4\n\n;\n"));
+	}
+
+	#[test]
+	fn omnispeak_section_vars() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n\t\"CREDITS\"\n}\nBitmapsMasked {\n\t\"STATUS\"\n}\nSprites {\n\t\"KEEN\"\n}\nDemo 0\n",
+		).unwrap();
+		let mut omnispeak_options = OmnispeakOptions::default();
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.contains("%int PIC_CREDITS 4\n%int NUMPICS 2\n%int STARTPICS 3\n"));
+		assert!(cfg.contains("%int NUMPICM 1\n%int STARTPICM 5\n# Sprites\n"));
+		assert!(cfg.contains("%int SPR_KEEN 6\n%int NUMSPRITES 1\n%int STARTSPRITES 6\n"));
+
+		omnispeak_options.emit_section_vars = false;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(!cfg.contains("NUMPICS"));
+		assert!(!cfg.contains("STARTSPRITES"));
+	}
+
+	#[test]
+	fn igrab_extra_defines() {
+		let headers = parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\nDemo 0\n").unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		igrab_options
+			.extra_defines
+			.push(("GRAPHICSEG".to_string(), "0xA000".to_string()));
+		igrab_options
+			.extra_defines
+			.push(("SCREENWIDTH".to_string(), "40".to_string()));
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains(
+			"#define STARTEXTERNS 4\n\n#define GRAPHICSEG 0xA000\n#define SCREENWIDTH 40\n\n//\n// Thank you"
+		));
+	}
+
+	#[test]
+	fn lump_covers_all_sprites() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n}\nSprites {\n\tLump \"ALL_SPRITES\" {\n\t\t\"A\"\n\t\t\"B\"\n\t\t\"C\"\n\t}\n}\n",
+		).unwrap();
+		assert_eq!(headers.lumps.len(), 1);
+		let lump = &headers.lumps[0];
+		assert_eq!(lump.start_chunk, headers.sprites_start());
+		assert_eq!(lump.end_chunk, headers.sprites_start() + 3 - 1);
+		assert_eq!(lump.end_chunk, headers.misc_start() - 1);
+	}
+
+	#[test]
+	fn igrab_extension_line() {
+		let headers =
+			parse_gfx_script_str("Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\n")
+				.unwrap();
+		let igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert_eq!(header.lines().nth(2), Some("// Graphics .H file for CK4"));
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert_eq!(asm.lines().nth(2), Some("; Graphics .H file for .CK4"));
+	}
+
+	#[test]
+	fn omnispeak_masked_bitmap_lump_names() {
+		let headers = parse_gfx_script_str(
+			"BitmapsMasked {\n\tLump \"STATUS\" {\n\t\t\"STATUSLEFT\"\n\t\t\"STATUSRIGHT\"\n\t}\n}\nDemo 0\n",
+		).unwrap();
+		let cfg = omnispeak_cfg_string(&headers, &OmnispeakOptions::default());
+		assert!(cfg.contains("%int MPIC_STATUSLEFT 3\n"));
+		assert!(cfg.contains("%int MPIC_STATUSRIGHT 4\n"));
+		assert!(cfg.contains("%intarray lumpStarts\n\t@MPIC_STATUSLEFT\n"));
+		assert!(cfg.contains("%intarray lumpEnds\n\t@MPIC_STATUSRIGHT\n"));
+	}
+
+	#[test]
+	fn igrab_pragma_once() {
+		let headers = parse_gfx_script_str("Bitmaps {\n\t\"TITLE\"\n}\n").unwrap();
+		let mut igrab_options = IGrabOptions::default();
+		assert!(!igrab_header_string(&headers, &igrab_options).contains("#pragma"));
+
+		igrab_options.pragma_once = true;
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header
+			.starts_with("#pragma once\n//////////////////////////////////////\n"));
+		assert!(!igrab_asm_header_string(&headers, &igrab_options).contains("#pragma"));
+	}
+
+	#[test]
+	fn omnispeak_generator_comment() {
+		let headers = parse_gfx_script_str("Demo 0\n").unwrap();
+		let mut omnispeak_options = OmnispeakOptions {
+			source_filename: Some("keen4.idgrab".to_string()),
+			..Default::default()
+		};
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.starts_with(&format!(
+			"# GFX Header generated by idGrab {}\n# Source: keen4.idgrab\n\n%int STRUCTPIC 0\n",
+			IDGRAB_VERSION.unwrap()
+		)));
+
+		omnispeak_options.emit_generator_comment = false;
+		let cfg = omnispeak_cfg_string(&headers, &omnispeak_options);
+		assert!(cfg.starts_with("%int STRUCTPIC 0\n"));
+		assert!(!cfg.contains("idGrab"));
+	}
+
+	#[test]
+	fn modid_version_comment() {
+		let headers = parse_gfx_script_str("Demo 0\n").unwrap();
+		let mut modid_options = ModidOptions {
+			source_filename: Some("keen4.idgrab".to_string()),
+			..Default::default()
+		};
+		let mut out = Vec::new();
+		headers.write_modid_script(&mut out, &modid_options)
+			.unwrap();
+		assert!(String::from_utf8(out).unwrap().starts_with(&format!(
+			"# ModID Script: Automatically Generated\n# Generated by idGrab {}\n# Source: keen4.idgrab\nGALAXY\n",
+			IDGRAB_VERSION.unwrap()
+		)));
+
+		modid_options.emit_version_comment = false;
+		let mut out = Vec::new();
+		headers.write_modid_script(&mut out, &modid_options)
+			.unwrap();
+		assert!(String::from_utf8(out)
+			.unwrap()
+			.starts_with("# ModID Script: Automatically Generated\nGALAXY\n"));
+	}
+
+	#[test]
+	fn script_round_trip_escapes() {
+		let headers = parse_gfx_script_str(
+			"Extension \"C\\\\K4\"\nBitmaps {\n\tLump \"\\\"Q\\\"\" {\n\t\t\"A\\tB\"\n\t}\n}\nArticle \"HELP\\n\"\n",
+		)
+		.unwrap();
+		assert_eq!(headers.extension.as_deref(), Some("C\\K4"));
+		assert_eq!(headers.bitmaps, vec!["A\tB".to_string()]);
+		let mut out = Vec::new();
+		headers.write_script(&mut out).unwrap();
+		assert_eq!(
+			parse_gfx_script_str(&String::from_utf8(out).unwrap()).unwrap(),
+			headers
+		);
+	}
+
+	#[test]
+	fn script_round_trip_empty_lumps() {
+		/* Both at the end of a section, and in a section of their own. */
+		for script in [
+			"Bitmaps {\n\t\"A\"\nLump \"E\" {\n}\n}\n\nSprites {\n\t\"S\"\n}\n\n",
+			"Bitmaps {\nLump \"E\" {\n}\n}\n\n",
+		]
+		.iter()
+		{
+			let headers = parse_gfx_script_str(script).unwrap();
+			let mut out = Vec::new();
+			headers.write_script(&mut out).unwrap();
+			let out = String::from_utf8(out).unwrap();
+			assert!(
+				out.ends_with(script),
+				"{:?} was written as {:?}",
+				script,
+				out
+			);
+			let reparsed = parse_gfx_script_str(&out).unwrap();
+			assert_eq!(reparsed, headers);
+			assert_eq!(reparsed.lumps[0].section, ChunkSection::Bitmaps);
+		}
+	}
+
+	#[test]
+	fn script_header_chunks() {
+		let headers = parse_gfx_script_str("HeaderChunks 2\nBitmaps {\n\t\"TITLE\"\n}\n")
+			.unwrap();
+		assert_eq!(headers.header_chunk_count, 2);
+		assert_eq!(headers.bitmaps_start(), 2);
+		assert_eq!(parse_gfx_script_str("").unwrap().header_chunk_count, 3);
+
+		/* HeaderChunks overrides the Game's default, whichever comes first. */
+		for script in [
+			"HeaderChunks 4\nGame Keen4\n",
+			"Game Keen4\nHeaderChunks 4\n",
+		]
+		.iter()
+		{
+			assert_eq!(parse_gfx_script_str(script).unwrap().header_chunk_count, 4);
+		}
+
+		let mut out = Vec::new();
+		headers.write_script(&mut out).unwrap();
+		let script = String::from_utf8(out).unwrap();
+		assert!(script.starts_with("HeaderChunks 2\n\n"));
+		assert_eq!(parse_gfx_script_str(&script).unwrap(), headers);
+	}
+
+	#[test]
+	fn igrab_header_chunks() {
+		let headers = parse_gfx_script_str("HeaderChunks 2\nBitmaps {\n\t\"TITLE\"\n}\n")
+			.unwrap();
+		let igrab_options = IGrabOptions::default();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(header.contains("\t\tTITLEPIC = 2,\n"));
+		assert!(header.contains(
+			"#define STRUCTPIC    0\n#define STRUCTPICM   1\n\n#define STARTFONT    2\n"
+		));
+		assert!(!header.contains("STRUCTSPRITE"));
+		assert!(header.contains("#define STARTPICS    2\n"));
+		let asm = igrab_asm_header_string(&headers, &igrab_options);
+		assert!(asm.contains("STRUCTPICM  \t=\t1\n\nSTARTFONT  \t=\t2\n"));
+
+		let headers = parse_gfx_script_str("HeaderChunks 0\nBitmaps {\n\t\"TITLE\"\n}\n")
+			.unwrap();
+		let header = igrab_header_string(&headers, &igrab_options);
+		assert!(!header.contains("STRUCT"));
+		assert!(header.contains("\t\tTITLEPIC = 0,\n"));
+	}
+
+	#[test]
+	fn script_empty_lump_at_chunk_zero() {
+		let error =
+			parse_gfx_script_str("HeaderChunks 0\nFonts {\n\tLump \"X\" {\n\t}\n}\n")
+				.unwrap_err();
+		assert_eq!(
+			error.to_string(),
+			"line 4: Lump \"X\" is empty, and starts at chunk 0."
+		);
+
+		/* Anywhere else, empty lumps still parse (and fail validation). */
+		let headers =
+			parse_gfx_script_str("HeaderChunks 1\nFonts {\n\tLump \"X\" {\n\t}\n}\n")
+				.unwrap();
+		assert_eq!(headers.lumps[0].start_chunk, 1);
+		assert_eq!(headers.lumps[0].end_chunk, 0);
+	}
+
+	#[test]
+	fn gfxinfoe_round_trip() {
+		let headers = parse_gfx_script("scripts/keen4.idgrab").unwrap();
+		let mut out = Vec::new();
+		headers.write_gfxinfoe(&mut out).unwrap();
+		let read_back = GfxHeaders::read_gfxinfoe(&mut &out[..]).unwrap();
+		assert_eq!(read_back.fonts_start(), headers.fonts_start());
+		assert_eq!(read_back.bitmaps_start(), headers.bitmaps_start());
+		assert_eq!(read_back.bitmaps.len(), headers.bitmaps.len());
+		assert_eq!(read_back.bitmaps_masked.len(), headers.bitmaps_masked.len());
+		assert_eq!(read_back.sprites.len(), headers.sprites.len());
+		assert_eq!(read_back.sprites_start(), headers.sprites_start());
+		assert_eq!(read_back.tile8_count, headers.tile8_count);
+		assert_eq!(read_back.tile16_count, headers.tile16_count);
+		assert_eq!(read_back.tile32_masked_count, headers.tile32_masked_count);
+		assert_eq!(read_back.misc_chunks.len(), headers.misc_chunks.len());
+		assert_eq!(read_back.misc_start(), headers.misc_start());
+		assert_eq!(read_back.bitmaps[0], "PIC0000");
+		assert_eq!(read_back.sprites[1], "SPR0001");
+
+		let mut rewritten = Vec::new();
+		read_back.write_gfxinfoe(&mut rewritten).unwrap();
+		assert_eq!(rewritten, out);
+
+		/* A truncated file, or one whose starts don't add up, is an error. */
+		assert!(GfxHeaders::read_gfxinfoe(&mut &out[..out.len() - 1]).is_err());
+		let mut bad = out.clone();
+		bad[44] += 1;
+		assert_eq!(
+			GfxHeaders::read_gfxinfoe(&mut &bad[..]).unwrap_err().kind(),
+			std::io::ErrorKind::InvalidData
+		);
+	}
+
+	#[test]
+	fn gfxinfoe_needs_three_header_chunks() {
+		let headers = parse_gfx_script_str("HeaderChunks 2\nBitmaps {\n\t\"TITLE\"\n}\n")
+			.unwrap();
+		let mut out = Vec::new();
+		assert_eq!(
+			headers.write_gfxinfoe(&mut out).unwrap_err().kind(),
+			std::io::ErrorKind::InvalidInput
+		);
+		assert!(out.is_empty());
+	}
+}
@@ -0,0 +1,91 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+#[derive(Clone)]
+pub struct ModidOptions {
+	/* Leave sections with no chunks out of the GALAXY block, rather than writing a count of 0. */
+	pub skip_empty_sections: bool,
+	/*
+	 * Write TILE8/TILE8M sections even with no tiles. Older modid versions
+	 * left them out, and some tools reject a count of 0.
+	 */
+	pub explicit_zero_tile8: bool,
+	/* Add comments saying where each section and lump starts. */
+	pub emit_comments: bool,
+	/* Say which idGrab version (and script) the file came from, after the first comment. */
+	pub emit_version_comment: bool,
+	/* The script's filename, for the version comment. */
+	pub source_filename: Option<String>,
+}
+
+impl Default for ModidOptions {
+	fn default() -> ModidOptions {
+		ModidOptions {
+			skip_empty_sections: false,
+			explicit_zero_tile8: false,
+			emit_comments: false,
+			emit_version_comment: true,
+			source_filename: None,
+		}
+	}
+}
+
+/* What a GALAXY block section contains, for comments. */
+fn section_description(section_name: &str) -> &str {
+	match section_name {
+		"FONT" => "Fonts",
+		"FONTM" => "Masked fonts",
+		"PICS" => "Bitmaps",
+		"PICM" => "Masked bitmaps",
+		"SPRITES" => "Sprites",
+		"TILE8" => "Tile8s",
+		"TILE8M" => "Masked tile8s",
+		"TILE16" => "Tile16s",
+		"TILE16M" => "Masked tile16s",
+		"TILE32" => "Tile32s",
+		"TILE32M" => "Masked tile32s",
+		_ => section_name,
+	}
+}
+
+impl ModidOptions {
+	/* Writes a "NAME count start [struct]" section line, e.g. "PICS 10 6 0". */
+	pub fn write_section(
+		&self,
+		f: &mut dyn std::io::Write,
+		section_name: &str,
+		count: u32,
+		start_chunk: u32,
+		struct_chunk: Option<u32>,
+	) -> std::io::Result<()> {
+		if count == 0 && self.skip_empty_sections {
+			return Ok(());
+		}
+		if self.emit_comments {
+			writeln!(f, "\t\t# {} start here", section_description(section_name))?;
+		}
+		write!(f, "\t\t{}\t\t{} {}", section_name, count, start_chunk)?;
+		if let Some(struct_chunk) = struct_chunk {
+			write!(f, " {}", struct_chunk)?;
+		}
+		writeln!(f)
+	}
+}
@@ -0,0 +1,144 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+use parser::quote_string;
+
+#[derive(Clone)]
+pub struct OmnispeakOptions {
+	/* Start with a comment naming the idGrab version (and script) the file came from. */
+	pub emit_generator_comment: bool,
+	/* The script's filename, for the generator comment. */
+	pub source_filename: Option<String>,
+	/* Emit each named section as a pair of %strarray/%intarray blocks instead of one %int per chunk. */
+	pub use_arrays_for_sections: bool,
+	/* Emit %string gameExt "EXT" if the script has an extension. */
+	pub emit_game_ext: bool,
+	/*
+	 * Emit the font section variables, NUMFONT(M)/STARTFONT(M), after the
+	 * masked fonts. These use the same names as the IGRAB headers, as the
+	 * tile variables do.
+	 */
+	pub emit_font_vars: bool,
+	/*
+	 * Emit NUMFONTS/FONTS_START and NUMFONTSM/FONTSM_START, the names
+	 * Omnispeak reads when setting up its fonts.
+	 */
+	pub emit_font_section_vars: bool,
+	/* Emit NUMTILEx/STARTTILEx variables for each tile type. */
+	pub emit_tile_vars: bool,
+	/* Emit NUMx/STARTx variables for bitmaps, masked bitmaps and sprites. */
+	pub emit_section_vars: bool,
+	/* Names the header chunks STRUCTSLOT_0, etc., if there aren't the usual three. */
+	pub struct_slot_prefix: String,
+	/* Also emit STARTDEMO (the same as DEMOSTART) and NUMDEMOS. */
+	pub emit_demo_aliases: bool,
+	/* Variable name prefixes for each kind of misc chunk. */
+	pub chunk_prefix: String,
+	pub b8000text_prefix: String,
+	pub article_prefix: String,
+	pub terminator_prefix: String,
+}
+
+impl Default for OmnispeakOptions {
+	fn default() -> OmnispeakOptions {
+		OmnispeakOptions {
+			emit_generator_comment: true,
+			source_filename: None,
+			use_arrays_for_sections: false,
+			emit_game_ext: true,
+			emit_font_vars: true,
+			emit_font_section_vars: true,
+			emit_tile_vars: true,
+			emit_section_vars: true,
+			struct_slot_prefix: "STRUCTSLOT_".to_string(),
+			emit_demo_aliases: false,
+			chunk_prefix: "EXTERN_".to_string(),
+			b8000text_prefix: "EXTERN_".to_string(),
+			article_prefix: "TEXT_".to_string(),
+			terminator_prefix: "EXTERN_".to_string(),
+		}
+	}
+}
+
+impl OmnispeakOptions {
+	/*
+	 * Writes the variables for a section of named chunks, either as
+	 * "%int PREFIX_NAME N" lines, or as "%strarray xNames" and
+	 * "%intarray xStarts" blocks.
+	 */
+	pub fn write_section(
+		&self,
+		f: &mut dyn std::io::Write,
+		var_prefix: &str,
+		array_prefix: &str,
+		names: &[String],
+		start_chunk: u32,
+	) -> std::io::Result<()> {
+		if !self.use_arrays_for_sections {
+			for (chunk_id, name) in (start_chunk..).zip(names) {
+				writeln!(f, "%int {}_{} {}", var_prefix, name, chunk_id)?;
+			}
+			return Ok(());
+		}
+
+		if names.is_empty() {
+			return Ok(());
+		}
+		let quoted_names: Vec<String> =
+			names.iter().map(|name| quote_string(name)).collect();
+		write_array(
+			f,
+			"%strarray",
+			&format!("{}Names", array_prefix),
+			&quoted_names,
+		)?;
+		let chunk_ids: Vec<String> = (start_chunk..)
+			.take(names.len())
+			.map(|chunk_id| chunk_id.to_string())
+			.collect();
+		write_array(
+			f,
+			"%intarray",
+			&format!("{}Starts", array_prefix),
+			&chunk_ids,
+		)
+	}
+}
+
+/* Writes an Omnispeak array variable, with one (comma-separated) value per line. */
+pub fn write_array(
+	f: &mut dyn std::io::Write,
+	array_type: &str,
+	name: &str,
+	values: &[String],
+) -> std::io::Result<()> {
+	writeln!(f, "{} {}", array_type, name)?;
+	let mut value_iterator = values.iter().peekable();
+	while let Some(value) = value_iterator.next() {
+		let comma = if value_iterator.peek().is_none() {
+			""
+		} else {
+			","
+		};
+		writeln!(f, "\t{}{}", value, comma)?;
+	}
+	Ok(())
+}
@@ -19,34 +19,217 @@
  *   3. This notice may not be removed or altered from any source distribution.
  */
 
+use std::borrow::Cow;
+
 #[derive(PartialEq, Debug)]
-pub enum Token<'a> {
-	Ident(&'a str),
+pub enum Token {
+	Ident(String),
 	Symbol(char),
 	StringLiteral(String),
 	NumericLiteral(i64),
 }
 
+/* A token, along with the (1-based) line and column at which it started. */
+#[derive(PartialEq, Debug)]
+pub struct TokenWithLocation {
+	pub token: Token,
+	pub line: usize,
+	pub col: usize,
+}
+
+/* A problem with a script, which stops it from being parsed. */
+#[derive(PartialEq, Debug)]
+pub struct ParseError {
+	/* Where the error is: "file:line", or "line N" if there's no filename. */
+	pub location: String,
+	/* The line the error is on, or 0 if the script couldn't be read at all. */
+	pub line: usize,
+	/* What was expected, and what was found instead, for unexpected tokens. */
+	pub expected: Option<String>,
+	pub found: Option<String>,
+	pub message: String,
+	/* The text of the line the error is on, to show after the message. */
+	pub line_content: Option<String>,
+}
+
+impl std::fmt::Display for ParseError {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		write!(f, "{}: {}", self.location, self.message)?;
+		if let Some(line_content) = &self.line_content {
+			write!(f, "\n\t{}", line_content)?;
+		}
+		Ok(())
+	}
+}
+
+/* ParseErrors are boxed, as they're much bigger than most of what's parsed. */
+pub type ParseResult<T> = Result<T, Box<ParseError>>;
+
+/* Quotes a string for a script, escaping anything the lexer would otherwise misread. */
+pub fn quote_string(s: &str) -> String {
+	let mut quoted = String::from("\"");
+	for c in s.chars() {
+		match c {
+			'"' => quoted.push_str("\\\""),
+			'\\' => quoted.push_str("\\\\"),
+			'\n' => quoted.push_str("\\n"),
+			'\t' => quoted.push_str("\\t"),
+			c => quoted.push(c),
+		}
+	}
+	quoted.push('"');
+	quoted
+}
+
+/* Settings for how scripts are lexed. */
+#[derive(Default, Clone, Copy)]
+pub struct ParserOptions {
+	/* Treat ';' like '#', for scripts written with assembly-style comments. */
+	pub allow_semicolon_comments: bool,
+}
+
+/*
+ * Lexers either borrow the script (from_str), or own it (from_file), so
+ * tokens own their text rather than borrowing it.
+ */
 pub struct Lexer<'a> {
-	data: &'a str,
+	data: Cow<'a, str>,
+	/* The file being lexed, if any, for error messages. */
+	filename: Option<String>,
+	options: ParserOptions,
 	offset: usize,
 	line: usize,
-	buffered_token: Option<Token<'a>>,
+	line_start: usize,
+	last_location: (usize, usize),
+	last_offset: usize,
+	buffered_token: Option<TokenWithLocation>,
 }
 
 impl<'a> Lexer<'a> {
 	pub fn from_str(data: &'a str) -> Lexer<'a> {
 		Lexer {
-			data,
+			data: Cow::Borrowed(data),
+			filename: None,
+			options: ParserOptions::default(),
 			offset: 0,
 			line: 1,
+			line_start: 0,
+			last_location: (1, 1),
+			last_offset: 0,
 			buffered_token: None,
 		}
 	}
 
-	fn unget_token(&mut self, token: Token<'a>) {
-		assert!(self.buffered_token.is_none());
-		self.buffered_token = Some(token);
+	// Creates a lexer which owns the contents of the given file.
+	pub fn from_file(path: &std::path::Path) -> std::io::Result<Lexer<'static>> {
+		let data = std::fs::read_to_string(path)?;
+		let lexer = Lexer {
+			data: Cow::Owned(data),
+			..Lexer::from_str("")
+		};
+		Ok(lexer.with_filename(path))
+	}
+
+	/* Sets the filename shown in error messages. */
+	pub fn with_filename(mut self, path: &std::path::Path) -> Lexer<'a> {
+		self.filename = Some(path.display().to_string());
+		self
+	}
+
+	pub fn with_options(mut self, options: ParserOptions) -> Lexer<'a> {
+		self.options = options;
+		self
+	}
+
+	/* Whether a comment starts at the current offset. */
+	fn at_comment_start(&self) -> bool {
+		let rest = &self.data[self.offset..];
+		rest.starts_with('#')
+			|| rest.starts_with("//")
+			|| (rest.starts_with(';') && self.options.allow_semicolon_comments)
+	}
+
+	/*
+	 * Describes where the most recently read token is, for error messages:
+	 * "file:line" if the filename is known, otherwise "line N".
+	 */
+	pub fn error_location(&self) -> String {
+		match &self.filename {
+			Some(filename) => format!("{}:{}", filename, self.last_location.0),
+			None => format!("line {}", self.last_location.0),
+		}
+	}
+
+	// The line the most recently read token started on.
+	pub fn last_line(&self) -> usize {
+		self.last_location.0
+	}
+
+	/* An error at the most recently read token. */
+	pub fn error(&self, message: String) -> Box<ParseError> {
+		Box::new(ParseError {
+			location: self.error_location(),
+			line: self.last_location.0,
+			expected: None,
+			found: None,
+			message,
+			line_content: None,
+		})
+	}
+
+	/* An error for finding the given token (or EOF, if None) instead of what was expected. */
+	pub fn unexpected(&self, expected: &str, found: Option<&Token>) -> Box<ParseError> {
+		/* There's no line to show at EOF. */
+		let (found, line_content) = match found {
+			Some(token) => (
+				format!("{:?}", token),
+				Some(self.line_content().to_string()),
+			),
+			None => ("EOF".to_string(), None),
+		};
+		let message = format!("Expected {}, but got {}!", expected, found);
+		Box::new(ParseError {
+			expected: Some(expected.to_string()),
+			found: Some(found),
+			line_content,
+			..*self.error(message)
+		})
+	}
+
+	/* An error for a token which doesn't belong where it is. */
+	pub fn unknown_token(&self, found: Option<&Token>) -> Box<ParseError> {
+		Box::new(ParseError {
+			found: found.map(|token| format!("{:?}", token)),
+			..*self.error("Unknown token!".to_string())
+		})
+	}
+
+	// Pushes a token back, so that it is returned by the next call to
+	// next_token(). Only one token of lookahead is supported: this panics
+	// if a token has already been pushed back and not yet re-read.
+	//
+	// The token is assumed to be the one most recently read, and keeps
+	// that token's location.
+	pub fn unget_token(&mut self, token: Token) {
+		assert!(
+			self.buffered_token.is_none(),
+			"Tried to unget more than one token!"
+		);
+		let (line, col) = self.last_location;
+		self.buffered_token = Some(TokenWithLocation { token, line, col });
+	}
+
+	/*
+	 * The full text of the line on which the most recently read token started,
+	 * without its line ending, for showing in error messages.
+	 */
+	pub fn line_content(&self) -> &str {
+		let data = &*self.data;
+		let start = data[..self.last_offset].rfind('\n').map_or(0, |i| i + 1);
+		let end = data[self.last_offset..]
+			.find('\n')
+			.map_or(data.len(), |i| self.last_offset + i);
+		data[start..end].trim_end_matches('\r')
 	}
 
 	fn peek_char(&self) -> Option<char> {
@@ -55,27 +238,138 @@ impl<'a> Lexer<'a> {
 
 	fn eat_char(&mut self) {
 		let c = self.peek_char().unwrap();
+		self.offset += c.len_utf8();
 		if c == '\n' {
 			self.line += 1;
+			self.line_start = self.offset;
 		}
-		self.offset += c.len_utf8();
 	}
 
+	fn col(&self) -> usize {
+		self.data[self.line_start..self.offset].chars().count() + 1
+	}
+
+	/*
+	 * Skips whitespace and comments, which run from '#' or '//' (or ';', if
+	 * allowed) to the end of the line. The '\r' of CRLF line endings counts
+	 * as whitespace.
+	 *
+	 * This means editor mode lines, such as "// -*- mode: idgrab -*-" for
+	 * Emacs (see contrib/idgrab-mode.el), can go at the top of a script.
+	 */
 	fn eat_whitespace(&mut self) {
-		loop {
-			let c = self.peek_char();
-			if c.is_none() {
+		while let Some(c) = self.peek_char() {
+			if self.at_comment_start() {
+				while let Some(comment_c) = self.peek_char() {
+					self.eat_char();
+					if comment_c == '\n' {
+						break;
+					}
+				}
+			} else if c.is_whitespace() {
+				self.eat_char();
+			} else {
 				break;
 			}
-			if !c.unwrap().is_whitespace() {
+		}
+	}
+
+	pub fn next_token(&mut self) -> ParseResult<Option<Token>> {
+		Ok(self.next_token_with_location()?.map(|t| t.token))
+	}
+
+	pub fn next_token_with_location(&mut self) -> ParseResult<Option<TokenWithLocation>> {
+		if let Some(token) = self.buffered_token.take() {
+			return Ok(Some(token));
+		}
+		self.eat_whitespace();
+		let line = self.line;
+		let col = self.col();
+		self.last_location = (line, col);
+		self.last_offset = self.offset;
+		Ok(self.lex_token()?
+			.map(|token| TokenWithLocation { token, line, col }))
+	}
+
+	/*
+	 * Reads the rest of an escape sequence in a string literal, after the
+	 * backslash. Only \", \\, \n and \t are supported.
+	 */
+	fn lex_escape(&mut self) -> ParseResult<char> {
+		let escaped = match self.peek_char() {
+			Some('"') => '"',
+			Some('\\') => '\\',
+			Some('n') => '\n',
+			Some('t') => '\t',
+			Some(c) => {
+				return Err(self.error(format!(
+					"Unknown escape sequence \\{} in string literal!",
+					c
+				)));
+			}
+			None => {
+				return Err(self.error(
+					"Unexpected end of file (missing '\"')".to_string()
+				));
+			}
+		};
+		self.eat_char();
+		Ok(escaped)
+	}
+
+	/*
+	 * Reads an integer literal: decimal, or hexadecimal or binary with a 0x
+	 * or 0b prefix. Any of them can be negative.
+	 */
+	fn lex_int_literal(&mut self) -> ParseResult<i64> {
+		let start_offset = self.offset;
+		if self.peek_char() == Some('-') {
+			self.eat_char();
+		}
+		let rest = &self.data[self.offset..];
+		let radix = if rest.starts_with("0x") || rest.starts_with("0X") {
+			16
+		} else if rest.starts_with("0b") || rest.starts_with("0B") {
+			2
+		} else {
+			10
+		};
+		if radix != 10 {
+			self.eat_char();
+			self.eat_char();
+		}
+		let digits_offset = self.offset;
+		/* Take any letters too, so that e.g. "0xFG" is an error, not 0xF and G. */
+		while let Some(int_c) = self.peek_char() {
+			if !(int_c.is_numeric() || (radix != 10 && int_c.is_alphanumeric())) {
 				break;
 			}
 			self.eat_char();
 		}
+		let int_slice = &self.data[start_offset..self.offset];
+		let digits = &self.data[digits_offset..self.offset];
+		/*
+		 * C would read a leading 0 as octal, but Rust's parse()
+		 * would silently treat it as decimal, so reject it rather
+		 * than guess.
+		 */
+		if radix == 10 && digits.len() > 1 && digits.starts_with('0') {
+			return Err(self.error(format!(
+				"Integer literal {} has a leading 0 (octal isn't supported)!",
+				int_slice
+			)));
+		}
+		let int_val = i64::from_str_radix(digits, radix).map_err(|_| {
+			self.error(format!("Invalid integer literal {}!", int_slice))
+		})?;
+		if int_slice.starts_with('-') {
+			Ok(-int_val)
+		} else {
+			Ok(int_val)
+		}
 	}
 
-	pub fn next_token(&mut self) -> Option<Token<'a>> {
-		self.eat_whitespace();
+	fn lex_token(&mut self) -> ParseResult<Option<Token>> {
 		let start_offset = self.offset;
 		loop {
 			let opt_c = self.peek_char();
@@ -84,19 +378,9 @@ impl<'a> Lexer<'a> {
 					break;
 				}
 				Some(c) => {
-					if c == '#' {
-						// Start of a comment.
-						loop {
-							let comment_c = self.peek_char();
-							if comment_c.is_none() {
-								break;
-							}
-							if comment_c.unwrap() == '\n' {
-								self.eat_char();
-								break;
-							}
-							self.eat_char();
-						}
+					if self.at_comment_start() {
+						// A comment ends the current token.
+						break;
 					} else if c == '"' {
 						// Start of a string literal.
 						let mut str_val = String::new();
@@ -105,44 +389,42 @@ impl<'a> Lexer<'a> {
 						loop {
 							let str_c = self.peek_char();
 							if str_c.is_none() {
-								panic!("Unexpected end of file (missing '\"') on line {}", self.line);
+								return Err(self.error(
+									"Unexpected end of file (missing '\"')"
+										.to_string(),
+								));
 							}
 							self.eat_char();
-							if str_c.unwrap() == '\"' {
-								break;
+							match str_c.unwrap() {
+								'"' => break,
+								/*
+								 * Strip '\r's, which Windows editors leave
+								 * in strings spanning CRLF lines.
+								 */
+								'\r' => {}
+								'\\' => {
+									let escaped =
+										self.lex_escape()?;
+									str_val.push(escaped);
+								}
+								c => str_val.push(c),
 							}
-							str_val.push(str_c.unwrap());
 						}
-						return Some(Token::StringLiteral(str_val));
+						return Ok(Some(Token::StringLiteral(str_val)));
 					} else if start_offset == self.offset
 						&& (c.is_numeric() || c == '-')
 					{
 						// Start of a numeric (integer) literal.
-						self.eat_char();
-						loop {
-							let int_c = self.peek_char();
-							if int_c.is_none()
-								|| !int_c.unwrap().is_numeric()
-							{
-								break;
-							}
-							self.eat_char();
-						}
-						let int_slice =
-							&self.data[start_offset..self.offset];
-						let int_val = int_slice.parse::<i64>().unwrap();
-						return Some(Token::NumericLiteral(int_val));
+						let int_val = self.lex_int_literal()?;
+						return Ok(Some(Token::NumericLiteral(int_val)));
 					} else if c.is_whitespace() {
-						if c == '\n' {
-							self.line += 1;
-						}
 						break;
 					} else if !c.is_alphanumeric() && c != '_' {
 						if self.offset != start_offset {
 							break;
 						}
 						self.eat_char();
-						return Some(Token::Symbol(c));
+						return Ok(Some(Token::Symbol(c)));
 					} else {
 						self.eat_char();
 					}
@@ -151,72 +433,54 @@ impl<'a> Lexer<'a> {
 		}
 		let end_offset = self.offset;
 		if start_offset == end_offset {
-			return None;
+			return Ok(None);
 		}
-		Some(Token::Ident(&self.data[start_offset..end_offset]))
+		Ok(Some(Token::Ident(
+			self.data[start_offset..end_offset].to_string(),
+		)))
 	}
 
-	pub fn expect_ident(&mut self, ident: &str) {
-		let line = self.line;
-		let tok = self.next_token();
-		if tok.is_none() {
-			panic!("Expected {} on line {}, but got EOF!", ident, line);
-		}
-		let tok_value = tok.unwrap();
-
-		if tok_value != Token::Ident(ident) {
-			panic!(
-				"Expected {} on line {}, but got {:?}!",
-				ident, line, tok_value
-			);
+	pub fn expect_ident(&mut self, ident: &str) -> ParseResult<()> {
+		match self.next_token()? {
+			Some(Token::Ident(tok_ident)) if tok_ident == ident => Ok(()),
+			tok => Err(self.unexpected(ident, tok.as_ref())),
 		}
 	}
 
-	pub fn expect_symbol(&mut self, sym: char) {
-		let line = self.line;
-		let tok = self.next_token();
-		if tok.is_none() {
-			panic!("Expected '{}' on line {}, but got EOF!", sym, line);
+	pub fn expect_symbol(&mut self, sym: char) -> ParseResult<()> {
+		match self.next_token()? {
+			Some(Token::Symbol(tok_sym)) if tok_sym == sym => Ok(()),
+			tok => Err(self.unexpected(&format!("'{}'", sym), tok.as_ref())),
 		}
-		let tok_value = tok.unwrap();
+	}
 
-		if tok_value != Token::Symbol(sym) {
-			panic!(
-				"Expected '{}' on line {}, but got {:?}!",
-				sym, line, tok_value
-			);
+	pub fn get_string_literal(&mut self) -> ParseResult<String> {
+		match self.next_token()? {
+			Some(Token::StringLiteral(str_val)) => Ok(str_val),
+			tok => Err(self.unexpected("string literal", tok.as_ref())),
 		}
 	}
 
-	pub fn get_string_literal(&mut self) -> String {
-		let line = self.line;
-		let tok = self.next_token();
-		if tok.is_none() {
-			panic!("Expected string literal on line {}, but got EOF!", line);
-		}
-		let tok_value = tok.unwrap();
-		if let Token::StringLiteral(str_val) = tok_value {
-			return str_val;
-		} else {
-			panic!("Expected string on line {}, but got {:?}!", line, tok_value);
+	pub fn get_int_literal(&mut self) -> ParseResult<i64> {
+		match self.next_token()? {
+			Some(Token::NumericLiteral(int_val)) => Ok(int_val),
+			tok => Err(self.unexpected("integer literal", tok.as_ref())),
 		}
 	}
 
-	pub fn get_int_literal(&mut self) -> i64 {
-		let line = self.line;
-		let tok = self.next_token();
-		if tok.is_none() {
-			panic!("Expected integer literal on line {}, but got EOF!", line);
-		}
-		let tok_value = tok.unwrap();
-		if let Token::NumericLiteral(int_val) = tok_value {
-			return int_val;
-		} else {
-			panic!(
-				"Expected integer literal on line {}, but got {:?}!",
-				line, tok_value
-			);
+	/* Gets an integer literal which is used as a count or chunk number. */
+	pub fn get_count_literal(&mut self) -> ParseResult<u32> {
+		let int_val = self.get_int_literal()?;
+		if int_val < 0 || int_val > i64::from(u32::MAX) {
+			return Err(Box::new(ParseError {
+				line_content: Some(self.line_content().to_string()),
+				..*self.error(format!(
+					"Expected a non-negative integer, but got {}!",
+					int_val
+				))
+			}));
 		}
+		Ok(int_val as u32)
 	}
 }
 
@@ -227,46 +491,290 @@ mod tests {
 	fn lexer_hello() {
 		let hello_world = "Hello World";
 		let mut lexer = Lexer::from_str(hello_world);
-		let first_token = lexer.next_token().unwrap();
-		assert_eq!(first_token, Token::Ident("Hello"));
-		let second_token = lexer.next_token().unwrap();
-		assert_eq!(second_token, Token::Ident("World"));
+		let first_token = lexer.next_token().unwrap().unwrap();
+		assert_eq!(first_token, Token::Ident("Hello".to_string()));
+		let second_token = lexer.next_token().unwrap().unwrap();
+		assert_eq!(second_token, Token::Ident("World".to_string()));
 
-		assert!(lexer.next_token().is_none());
+		assert!(lexer.next_token().unwrap().is_none());
 	}
 	#[test]
 	fn lexer_string_literal() {
 		let input = "  \" This is a string \" ";
 		let mut lexer = Lexer::from_str(input);
-		let token = lexer.next_token().unwrap();
+		let token = lexer.next_token().unwrap().unwrap();
 		assert_eq!(
 			token,
 			Token::StringLiteral(" This is a string ".to_string())
 		);
-		assert!(lexer.next_token().is_none());
+		assert!(lexer.next_token().unwrap().is_none());
 	}
 	#[test]
 	fn lexer_script() {
 		let test_input = "Filename=\"test.txt\"";
 		let mut lexer = Lexer::from_str(test_input);
-		assert_eq!(lexer.next_token().unwrap(), Token::Ident("Filename"));
-		assert_eq!(lexer.next_token().unwrap(), Token::Symbol('='));
 		assert_eq!(
-			lexer.next_token().unwrap(),
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Filename".to_string())
+		);
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol('='));
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
 			Token::StringLiteral("test.txt".to_string())
 		);
-		assert!(lexer.next_token().is_none());
+		assert!(lexer.next_token().unwrap().is_none());
 	}
 	#[test]
 	fn lexer_script_with_ws() {
 		let test_input = " Filename  =\n \"test.txt\"\n\n";
 		let mut lexer = Lexer::from_str(test_input);
-		assert_eq!(lexer.next_token().unwrap(), Token::Ident("Filename"));
-		assert_eq!(lexer.next_token().unwrap(), Token::Symbol('='));
 		assert_eq!(
-			lexer.next_token().unwrap(),
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Filename".to_string())
+		);
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol('='));
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
 			Token::StringLiteral("test.txt".to_string())
 		);
-		assert!(lexer.next_token().is_none());
+		assert!(lexer.next_token().unwrap().is_none());
+	}
+	#[test]
+	fn lexer_comments() {
+		let test_input = "# Leading comment\nBitmaps # Trailing comment\n{";
+		let mut lexer = Lexer::from_str(test_input);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Bitmaps".to_string())
+		);
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol('{'));
+		assert!(lexer.next_token().unwrap().is_none());
+	}
+	#[test]
+	fn lexer_string_escapes() {
+		let mut lexer = Lexer::from_str(r#""say \"hi\"" "C:\\KEEN" "a\nb" "a\tb""#);
+		let mut next_string = || match lexer.next_token().unwrap() {
+			Some(Token::StringLiteral(str_val)) => str_val,
+			tok => panic!("Expected a string, but got {:?}", tok),
+		};
+		assert_eq!(next_string(), "say \"hi\"");
+		assert_eq!(next_string(), "C:\\KEEN");
+		assert_eq!(next_string(), "a\nb");
+		assert_eq!(next_string(), "a\tb");
+		assert_eq!(
+			quote_string("say \"hi\"\tC:\\KEEN\n"),
+			r#""say \"hi\"\tC:\\KEEN\n""#
+		);
+	}
+	#[test]
+	fn lexer_bad_escapes() {
+		let mut lexer = Lexer::from_str("Chunk\n\"BAD\\q\"");
+		lexer.next_token().unwrap();
+		assert_eq!(
+			lexer.next_token().unwrap_err().to_string(),
+			"line 2: Unknown escape sequence \\q in string literal!"
+		);
+		let mut lexer = Lexer::from_str("\"TRAILING\\");
+		assert_eq!(
+			lexer.next_token().unwrap_err().to_string(),
+			"line 1: Unexpected end of file (missing '\"')"
+		);
+	}
+	#[test]
+	fn lexer_token_locations() {
+		let test_input = "Extension \"CK4\"\n\nFonts {\n\t\"MAINFONT\"\n}";
+		let mut lexer = Lexer::from_str(test_input);
+		let locations: Vec<(usize, usize)> =
+			std::iter::from_fn(|| lexer.next_token_with_location().unwrap())
+				.map(|t| (t.line, t.col))
+				.collect();
+		assert_eq!(
+			locations,
+			vec![(1, 1), (1, 11), (3, 1), (3, 7), (4, 2), (5, 1)]
+		);
+	}
+	#[test]
+	fn lexer_unget_token() {
+		let mut lexer = Lexer::from_str("Fonts {");
+		let token = lexer.next_token().unwrap().unwrap();
+		assert_eq!(token, Token::Ident("Fonts".to_string()));
+		lexer.unget_token(token);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Fonts".to_string())
+		);
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol('{'));
+		assert!(lexer.next_token().unwrap().is_none());
+	}
+	#[test]
+	#[should_panic]
+	fn lexer_unget_token_twice() {
+		let mut lexer = Lexer::from_str("Fonts {");
+		lexer.unget_token(Token::Ident("Fonts".to_string()));
+		lexer.unget_token(Token::Ident("Fonts".to_string()));
+	}
+	#[test]
+	fn lexer_from_file() {
+		let mut lexer =
+			Lexer::from_file(std::path::Path::new("scripts/keen4.idgrab")).unwrap();
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Extension".to_string())
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::StringLiteral("CK4".to_string())
+		);
+	}
+	#[test]
+	fn lexer_semicolon_comments() {
+		let script = "Chunk ; old-style comment\n\"README\"";
+		let mut lexer = Lexer::from_str(script);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Chunk".to_string())
+		);
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol(';'));
+
+		let mut lexer = Lexer::from_str(script).with_options(ParserOptions {
+			allow_semicolon_comments: true,
+		});
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Chunk".to_string())
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::StringLiteral("README".to_string())
+		);
+	}
+
+	#[test]
+	fn lexer_slash_comments() {
+		let mut lexer = Lexer::from_str(
+			"// -*- mode: idgrab -*-\nChunk// trailing\n\"README\" / \"X\"",
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::Ident("Chunk".to_string())
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::StringLiteral("README".to_string())
+		);
+		/* A single slash is still a symbol. */
+		assert_eq!(lexer.next_token().unwrap().unwrap(), Token::Symbol('/'));
+	}
+
+	#[test]
+	fn lexer_line_content() {
+		let mut lexer = Lexer::from_str("Extension \"CK4\"\r\nFonts {\n\t\"MAIN\nFONT\" }");
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.line_content(), "Extension \"CK4\"");
+		lexer.next_token().unwrap();
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.line_content(), "Fonts {");
+		/* A string spanning lines gives the line it started on. */
+		lexer.next_token().unwrap();
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.line_content(), "\t\"MAIN");
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.line_content(), "FONT\" }");
+	}
+
+	#[test]
+	fn lexer_crlf_line_endings() {
+		let mut lexer = Lexer::from_str(
+			"# Comment\r\nFonts {\r\n\t\"MAIN\r\"\r\n\t\"TWO\r\nLINES\"\r\n\t5\r\n}",
+		);
+		assert_eq!(
+			lexer.next_token().unwrap(),
+			Some(Token::Ident("Fonts".to_string()))
+		);
+		assert_eq!(lexer.last_line(), 2);
+		lexer.next_token().unwrap();
+		assert_eq!(
+			lexer.next_token().unwrap(),
+			Some(Token::StringLiteral("MAIN".to_string()))
+		);
+		assert_eq!(
+			lexer.next_token().unwrap(),
+			Some(Token::StringLiteral("TWO\nLINES".to_string()))
+		);
+		let error = lexer.get_string_literal().unwrap_err();
+		assert_eq!(error.line, 6);
+		assert_eq!(error.line_content.as_deref(), Some("\t5"));
+	}
+	#[test]
+	fn lexer_error_location() {
+		let mut lexer = Lexer::from_str("Fonts\n{");
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.error_location(), "line 1");
+		let mut lexer = lexer.with_filename(std::path::Path::new("subdir/script.gfx"));
+		lexer.next_token().unwrap();
+		assert_eq!(lexer.error_location(), "subdir/script.gfx:2");
+	}
+	#[test]
+	fn lexer_zero_literals() {
+		let mut lexer = Lexer::from_str("0 -0 10");
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::NumericLiteral(0)
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::NumericLiteral(0)
+		);
+		assert_eq!(
+			lexer.next_token().unwrap().unwrap(),
+			Token::NumericLiteral(10)
+		);
+	}
+	#[test]
+	fn lexer_hex_and_binary_literals() {
+		let mut lexer = Lexer::from_str("0xFF 0b1010 -0x10 0X1f 0B1");
+		for expected in [255, 10, -16, 31, 1].iter() {
+			assert_eq!(
+				lexer.next_token().unwrap().unwrap(),
+				Token::NumericLiteral(*expected)
+			);
+		}
+		assert!(lexer.next_token().unwrap().is_none());
+
+		for bad in ["0x", "0b", "0xFG", "0b102"].iter() {
+			assert_eq!(
+				Lexer::from_str(bad).next_token().unwrap_err().to_string(),
+				format!("line 1: Invalid integer literal {}!", bad)
+			);
+		}
+	}
+	#[test]
+	fn lexer_octal_literal() {
+		let mut lexer = Lexer::from_str("Tiles16 016");
+		lexer.expect_ident("Tiles16").unwrap();
+		assert_eq!(
+			lexer.get_int_literal().unwrap_err().to_string(),
+			"line 1: Integer literal 016 has a leading 0 (octal isn't supported)!"
+		);
+	}
+	#[test]
+	fn lexer_unexpected_token() {
+		let mut lexer = Lexer::from_str("Fonts\n\"MAIN\"");
+		lexer.expect_ident("Fonts").unwrap();
+		let error = lexer.expect_symbol('{').unwrap_err();
+		assert_eq!(error.line, 2);
+		assert_eq!(error.expected.as_deref(), Some("'{'"));
+		assert_eq!(error.found.as_deref(), Some("StringLiteral(\"MAIN\")"));
+		assert_eq!(
+			error.to_string(),
+			"line 2: Expected '{', but got StringLiteral(\"MAIN\")!\n\t\"MAIN\""
+		);
+
+		let error = lexer.get_string_literal().unwrap_err();
+		assert_eq!(error.found.as_deref(), Some("EOF"));
+		assert_eq!(
+			error.to_string(),
+			"line 2: Expected string literal, but got EOF!"
+		);
 	}
 }
@@ -0,0 +1,680 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+use igrab::IGRAB_NAMELEN;
+use ChunkSection;
+use GameVariant;
+use GfxHeaders;
+use MiscChunk;
+use GFXINFOE_HEADER_CHUNK_COUNT;
+
+/* C89, C99 and C11 keywords, which can't be used as (unsuffixed) chunk names. */
+const C_RESERVED_WORDS: &[&str] = &[
+	"auto",
+	"break",
+	"case",
+	"char",
+	"const",
+	"continue",
+	"default",
+	"do",
+	"double",
+	"else",
+	"enum",
+	"extern",
+	"float",
+	"for",
+	"goto",
+	"if",
+	"int",
+	"long",
+	"register",
+	"return",
+	"short",
+	"signed",
+	"sizeof",
+	"static",
+	"struct",
+	"switch",
+	"typedef",
+	"union",
+	"unsigned",
+	"void",
+	"volatile",
+	"while",
+	"inline",
+	"restrict",
+	"_Bool",
+	"_Complex",
+	"_Imaginary",
+	"_Alignas",
+	"_Alignof",
+	"_Atomic",
+	"_Generic",
+	"_Noreturn",
+	"_Static_assert",
+	"_Thread_local",
+];
+
+/* Problems with a script which don't stop us generating output, but may make it wrong. */
+#[derive(PartialEq, Debug)]
+pub enum ValidationWarning {
+	/* GFXINFOE always uses chunks 0, 1 and 2 for the picture/sprite tables. */
+	HeaderChunkCountMismatch(u32),
+	/* A setting which a Game's engine expects, e.g. Extension "CK4" for Keen4, overridden. */
+	GameSettingMismatch {
+		game: GameVariant,
+		keyword: &'static str,
+		expected: String,
+		found: String,
+	},
+	/* A named chunk which isn't part of any lump, so can't be streamed with one. */
+	UncoveredChunk {
+		chunk_id: u32,
+		name: String,
+	},
+	/* A lump which ends before it starts, i.e., an empty Lump { } block. */
+	InvalidLumpRange {
+		name: String,
+		line: usize,
+		start_chunk: u32,
+		end_chunk: u32,
+	},
+	/* A lump which starts in one section and ends in another. */
+	LumpCrossesSectionBoundary {
+		lump_name: String,
+		start_section: ChunkSection,
+		end_section: ChunkSection,
+	},
+	/* Demo chunks with other misc chunks between them, e.g. at chunk N. */
+	NonContiguousDemos(u32),
+	/* A demo which isn't numbered one more than the demo before it. */
+	NonSequentialDemos {
+		expected: u32,
+		found: u32,
+	},
+	/* A name IGRAB would have truncated to IGRAB_NAMELEN characters. */
+	NameExceedsMaxLength {
+		name: String,
+		len: usize,
+	},
+	/* A name used as-is in C headers which is a C keyword. */
+	PossibleReservedWordCollision {
+		name: String,
+	},
+	/* A TilesN block with a different number of names to the tile count. */
+	TileNameCountMismatch {
+		keyword: &'static str,
+		count: u32,
+		names: usize,
+	},
+}
+
+impl ValidationWarning {
+	/* Whether --strict-lumps should treat this warning as an error. */
+	pub fn is_lump_problem(&self) -> bool {
+		matches!(
+			self,
+			ValidationWarning::UncoveredChunk { .. }
+				| ValidationWarning::LumpCrossesSectionBoundary { .. }
+		)
+	}
+
+	/* Whether this always stops output being written, as GFXINFOE can't be. */
+	pub fn is_fatal(&self) -> bool {
+		matches!(self, ValidationWarning::HeaderChunkCountMismatch(_))
+	}
+
+	/* Whether --igrab-enforce-namelen should treat this warning as an error. */
+	pub fn is_name_length(&self) -> bool {
+		matches!(self, ValidationWarning::NameExceedsMaxLength { .. })
+	}
+}
+
+impl std::fmt::Display for ValidationWarning {
+	fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+		match self {
+			ValidationWarning::HeaderChunkCountMismatch(count) => write!(
+				f,
+				"There are {} header chunks, but GFXINFOE hardcodes chunks 0, 1 and 2 as the \
+				 STRUCTPIC, STRUCTPICM and STRUCTSPRITE tables.",
+				count
+			),
+			ValidationWarning::GameSettingMismatch {
+				game,
+				keyword,
+				expected,
+				found,
+			} => write!(
+				f,
+				"{} expects {} {}, but the script has {}.",
+				game, keyword, expected, found
+			),
+			ValidationWarning::UncoveredChunk { chunk_id, name } => {
+				write!(f, "Chunk {} ({}) isn't in any lump.", chunk_id, name)
+			}
+			ValidationWarning::InvalidLumpRange {
+				name,
+				line,
+				start_chunk,
+				end_chunk,
+			} => write!(
+				f,
+				"Lump \"{}\" (defined at line {}) ends at chunk {}, before it starts at chunk {}.",
+				name, line, end_chunk, start_chunk
+			),
+			ValidationWarning::LumpCrossesSectionBoundary {
+				lump_name,
+				start_section,
+				end_section,
+			} => write!(
+				f,
+				"Lump \"{}\" starts in the {}, but ends in the {}.",
+				lump_name, start_section, end_section
+			),
+			ValidationWarning::NonContiguousDemos(chunk_id) => write!(
+				f,
+				"The demos aren't all together: chunk {} comes between them, but \
+				 DEMOSTART assumes they're contiguous.",
+				chunk_id
+			),
+			ValidationWarning::NonSequentialDemos { expected, found } => write!(
+				f,
+				"Demo {} is where Demo {} should be: demos should be in order.",
+				found, expected
+			),
+			ValidationWarning::NameExceedsMaxLength { name, len } => write!(
+				f,
+				"{} is {} characters long, but IGRAB only allowed {}.",
+				name, len, IGRAB_NAMELEN
+			),
+			ValidationWarning::PossibleReservedWordCollision { name } => write!(
+				f,
+				"{} is a C keyword, so can't be used as a name in C headers.",
+				name
+			),
+			ValidationWarning::TileNameCountMismatch {
+				keyword,
+				count,
+				names,
+			} => write!(
+				f,
+				"There are {} {}, but {} names for them.",
+				count, keyword, names
+			),
+		}
+	}
+}
+
+impl GfxHeaders {
+	/*
+	 * Checks for anything which would produce inconsistent output. Lump
+	 * coverage is only checked on request, as the stock scripts only put
+	 * some of their chunks in lumps.
+	 */
+	pub fn validate(&self, uses_gfxinfoe: bool, check_lumps: bool) -> Vec<ValidationWarning> {
+		let mut warnings = Vec::new();
+		if uses_gfxinfoe && self.header_chunk_count != GFXINFOE_HEADER_CHUNK_COUNT {
+			warnings.push(ValidationWarning::HeaderChunkCountMismatch(
+				self.header_chunk_count,
+			));
+		}
+		/* Keen 4-6 load EGAGRAPH.CKn, and hardcode the three STRUCT* chunks. */
+		if let Some(expected) = self.game.default_extension() {
+			let found = self.extension.as_deref().unwrap_or("no Extension");
+			if found != expected {
+				warnings.push(ValidationWarning::GameSettingMismatch {
+					game: self.game,
+					keyword: "Extension",
+					expected: expected.to_string(),
+					found: found.to_string(),
+				});
+			}
+		}
+		if let Some(expected) = self.game.default_header_chunk_count() {
+			if self.header_chunk_count != expected {
+				warnings.push(ValidationWarning::GameSettingMismatch {
+					game: self.game,
+					keyword: "HeaderChunks",
+					expected: expected.to_string(),
+					found: self.header_chunk_count.to_string(),
+				});
+			}
+		}
+		for lump in &self.lumps {
+			if lump.start_chunk > lump.end_chunk {
+				warnings.push(ValidationWarning::InvalidLumpRange {
+					name: lump.name.clone(),
+					line: lump.defined_at_line,
+					start_chunk: lump.start_chunk,
+					end_chunk: lump.end_chunk,
+				});
+				continue;
+			}
+			/* Lumps can only contain chunks from the section they're defined in. */
+			let outside_section = [lump.start_chunk, lump.end_chunk]
+				.iter()
+				.filter_map(|&chunk| self.chunk_section(chunk))
+				.find(|&section| section != lump.section);
+			if let Some(end_section) = outside_section {
+				warnings.push(ValidationWarning::LumpCrossesSectionBoundary {
+					lump_name: lump.name.clone(),
+					start_section: lump.section,
+					end_section,
+				});
+			}
+		}
+		warnings.extend(self.check_demo_order());
+		for (name, _) in self.named_chunks() {
+			if name.len() > IGRAB_NAMELEN {
+				let len = name.len();
+				warnings.push(ValidationWarning::NameExceedsMaxLength {
+					name,
+					len,
+				});
+			}
+		}
+		/* Only fonts and misc chunks are written without a suffix. */
+		let misc_names = self.misc_chunks.iter().filter_map(|misc| match misc {
+			MiscChunk::Chunk(name)
+			| MiscChunk::Article(name)
+			| MiscChunk::B8000Text(name)
+			| MiscChunk::Terminator(name) => Some(name),
+			MiscChunk::Demo(_) => None,
+		});
+		for name in self
+			.fonts
+			.iter()
+			.chain(&self.fonts_masked)
+			.chain(misc_names)
+		{
+			if C_RESERVED_WORDS.contains(&name.as_str()) {
+				warnings.push(ValidationWarning::PossibleReservedWordCollision {
+					name: name.clone(),
+				});
+			}
+		}
+		let tile_names = [
+			("Tiles16", self.tile16_count, &self.tile16_names),
+			("Tiles32", self.tile32_count, &self.tile32_names),
+		];
+		for (keyword, count, names) in tile_names.iter() {
+			if let Some(names) = names {
+				if names.len() != *count as usize {
+					warnings.push(ValidationWarning::TileNameCountMismatch {
+						keyword,
+						count: *count,
+						names: names.len(),
+					});
+				}
+			}
+		}
+		if check_lumps {
+			let uncovered = self.check_lump_coverage();
+			for (name, chunk_id) in self.named_chunks() {
+				if uncovered.contains(&chunk_id) {
+					warnings.push(ValidationWarning::UncoveredChunk {
+						chunk_id,
+						name,
+					});
+				}
+			}
+		}
+		warnings
+	}
+
+	/*
+	 * Checks every name which ends up in C or assembly headers is a valid
+	 * identifier, returning the ones which aren't. Other formats don't care.
+	 */
+	pub fn check_c_identifiers(&self) -> Result<(), Vec<String>> {
+		let is_c_identifier = |name: &str| {
+			let mut chars = name.chars();
+			chars.next()
+				.is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+				&& chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+		};
+		let tile_names = self
+			.tile8_names
+			.iter()
+			.chain(self.tile16_names.iter().flatten())
+			.chain(self.tile32_names.iter().flatten())
+			.filter(|name| !name.is_empty());
+		let invalid_names: Vec<String> = self
+			.fonts
+			.iter()
+			.chain(&self.fonts_masked)
+			.chain(&self.bitmaps)
+			.chain(&self.bitmaps_masked)
+			.chain(&self.sprites)
+			.map(|name| name.as_str())
+			.chain(tile_names.map(|name| name.as_str()))
+			.chain(self.misc_chunks.iter().filter_map(|misc| misc.name()))
+			.chain(self.lumps.iter().map(|lump| lump.name.as_str()))
+			.filter(|name| !is_c_identifier(name))
+			.map(|name| name.to_string())
+			.collect();
+		if invalid_names.is_empty() {
+			Ok(())
+		} else {
+			Err(invalid_names)
+		}
+	}
+
+	/* Checks that the demos are contiguous, and numbered in order. */
+	fn check_demo_order(&self) -> Vec<ValidationWarning> {
+		let mut warnings = Vec::new();
+		let mut previous_demo: Option<u32> = None;
+		let mut gap: Option<u32> = None;
+		for (chunk_id, misc) in (self.misc_start()..).zip(&self.misc_chunks) {
+			match misc {
+				MiscChunk::Demo(num) => {
+					if let Some(gap_chunk) = gap.take() {
+						warnings.push(
+							ValidationWarning::NonContiguousDemos(
+								gap_chunk,
+							),
+						);
+					}
+					if let Some(previous) = previous_demo {
+						if *num != previous + 1 {
+							warnings.push(ValidationWarning::NonSequentialDemos {
+								expected: previous + 1,
+								found: *num,
+							});
+						}
+					}
+					previous_demo = Some(*num);
+				}
+				_ if previous_demo.is_some() && gap.is_none() => {
+					gap = Some(chunk_id);
+				}
+				_ => {}
+			}
+		}
+		warnings
+	}
+
+	/* The chunk numbers of any named chunks which aren't inside a lump. */
+	pub fn check_lump_coverage(&self) -> Vec<u32> {
+		self.named_chunks()
+			.into_iter()
+			.map(|(_, chunk_id)| chunk_id)
+			.filter(|chunk_id| {
+				!self.lumps.iter().any(|lump| {
+					(lump.start_chunk..=lump.end_chunk).contains(chunk_id)
+				})
+			})
+			.collect()
+	}
+}
+
+#[cfg(test)]
+mod tests {
+	use super::*;
+	use parse_gfx_script_str;
+	use Lump;
+
+	#[test]
+	fn validate_header_chunk_count() {
+		let mut headers = GfxHeaders {
+			header_chunk_count: 3,
+			..Default::default()
+		};
+		assert!(headers.validate(true, true).is_empty());
+		headers.header_chunk_count = 4;
+		assert_eq!(
+			headers.validate(true, true),
+			vec![ValidationWarning::HeaderChunkCountMismatch(4)]
+		);
+		assert!(headers.validate(false, true).is_empty());
+	}
+
+	#[test]
+	fn validate_game_settings() {
+		let headers = parse_gfx_script_str("Game Keen4\n").unwrap();
+		assert!(headers.validate(true, true).is_empty());
+
+		let headers = parse_gfx_script_str("Game Keen5\nExtension \"CK4\"\n").unwrap();
+		let warnings = headers.validate(false, false);
+		assert_eq!(
+			warnings,
+			vec![ValidationWarning::GameSettingMismatch {
+				game: GameVariant::Keen5,
+				keyword: "Extension",
+				expected: "CK5".to_string(),
+				found: "CK4".to_string(),
+			}]
+		);
+		assert_eq!(
+			warnings[0].to_string(),
+			"Keen5 expects Extension CK5, but the script has CK4."
+		);
+
+		let headers = parse_gfx_script_str("Game Keen6\nHeaderChunks 2\n").unwrap();
+		assert_eq!(
+			headers.validate(false, false)[0].to_string(),
+			"Keen6 expects HeaderChunks 3, but the script has 2."
+		);
+
+		/* Custom games can have anything. */
+		let headers = parse_gfx_script_str("Game Custom\nHeaderChunks 2\n").unwrap();
+		assert!(headers.validate(false, false).is_empty());
+	}
+
+	#[test]
+	fn validate_tile_names() {
+		let mut headers = GfxHeaders {
+			header_chunk_count: 3,
+			tile32_count: 2,
+			tile32_names: Some(vec!["CASTLE".to_string(), "TOWER".to_string()]),
+			..Default::default()
+		};
+		assert!(headers.validate(false, false).is_empty());
+		headers.tile32_count = 3;
+		assert_eq!(
+			headers.validate(false, false),
+			vec![ValidationWarning::TileNameCountMismatch {
+				keyword: "Tiles32",
+				count: 3,
+				names: 2
+			}]
+		);
+	}
+
+	#[test]
+	fn validate_empty_lump() {
+		let headers = parse_gfx_script_str(
+			"Bitmaps {\n\t\"TITLE\"\n\tLump \"EMPTY\" {\n\t}\n}\n",
+		)
+		.unwrap();
+		assert_eq!(headers.lumps[0].defined_at_line, 3);
+		let warnings = headers.validate(false, false);
+		assert_eq!(
+			warnings,
+			vec![ValidationWarning::InvalidLumpRange {
+				name: "EMPTY".to_string(),
+				line: 3,
+				start_chunk: 4,
+				end_chunk: 3
+			}]
+		);
+		assert_eq!(
+			warnings[0].to_string(),
+			"Lump \"EMPTY\" (defined at line 3) ends at chunk 3, before it starts at chunk 4."
+		);
+	}
+
+	#[test]
+	fn validate_lump_sections() {
+		let mut headers = parse_gfx_script_str(
+			"Bitmaps {\n\tLump \"TITLES\" {\n\t\t\"TITLE\"\n\t}\n}\nSprites {\n\t\"KEEN\"\n}\n",
+		).unwrap();
+		assert_eq!(headers.lumps[0].section, ChunkSection::Bitmaps);
+		assert!(headers.validate(false, false).is_empty());
+
+		headers.lumps[0].end_chunk = headers.sprites_start();
+		let warnings = headers.validate(false, false);
+		assert_eq!(
+			warnings,
+			vec![ValidationWarning::LumpCrossesSectionBoundary {
+				lump_name: "TITLES".to_string(),
+				start_section: ChunkSection::Bitmaps,
+				end_section: ChunkSection::Sprites,
+			}]
+		);
+		assert!(warnings[0].is_lump_problem());
+		assert_eq!(
+			warnings[0].to_string(),
+			"Lump \"TITLES\" starts in the bitmaps, but ends in the sprites."
+		);
+	}
+
+	#[test]
+	fn validate_demo_order() {
+		let headers =
+			parse_gfx_script_str("Chunk \"README\"\nDemo 0\nDemo 1\nChunk \"END\"\n")
+				.unwrap();
+		assert!(headers.validate(false, false).is_empty());
+
+		let headers = parse_gfx_script_str("Demo 1\nChunk \"FOO\"\nDemo 2\n").unwrap();
+		assert_eq!(
+			headers.validate(false, false),
+			vec![ValidationWarning::NonContiguousDemos(4)]
+		);
+
+		let headers = parse_gfx_script_str("Demo 0\nDemo 2\nDemo 1\n").unwrap();
+		assert_eq!(
+			headers.validate(false, false),
+			vec![
+				ValidationWarning::NonSequentialDemos {
+					expected: 1,
+					found: 2
+				},
+				ValidationWarning::NonSequentialDemos {
+					expected: 3,
+					found: 1
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn validate_name_length() {
+		let headers = parse_gfx_script_str(&format!(
+			"Bitmaps {{\n\t\"{}\"\n\t\"{}\"\n}}\n",
+			"A".repeat(29),
+			"B".repeat(30)
+		))
+		.unwrap();
+		/* The PIC suffix counts towards the limit. */
+		assert_eq!(
+			headers.validate(false, false),
+			vec![ValidationWarning::NameExceedsMaxLength {
+				name: format!("{}PIC", "B".repeat(30)),
+				len: 33
+			}]
+		);
+	}
+
+	#[test]
+	fn validate_reserved_words() {
+		let headers = parse_gfx_script_str(
+			"Fonts {\n\t\"char\"\n}\nBitmaps {\n\t\"int\"\n}\nChunk \"return\"\nChunk \"RETURN\"\n",
+		).unwrap();
+		/* intPIC is fine, and C is case sensitive. */
+		assert_eq!(
+			headers.validate(false, false),
+			vec![
+				ValidationWarning::PossibleReservedWordCollision {
+					name: "char".to_string()
+				},
+				ValidationWarning::PossibleReservedWordCollision {
+					name: "return".to_string()
+				},
+			]
+		);
+	}
+
+	#[test]
+	fn c_identifiers() {
+		let mut headers = GfxHeaders {
+			header_chunk_count: 3,
+			fonts: vec!["_MAIN".to_string()],
+			bitmaps: vec!["TITLE".to_string(), "MY PIC".to_string()],
+			sprites: vec!["1UP".to_string(), "KEEN_2".to_string()],
+			tile16_names: Some(vec!["".to_string(), "SKY-1".to_string()]),
+			misc_chunks: vec![
+				MiscChunk::Chunk("README.TXT".to_string()),
+				MiscChunk::Demo(0),
+			],
+			..Default::default()
+		};
+		assert_eq!(
+			headers.check_c_identifiers(),
+			Err(vec![
+				"MY PIC".to_string(),
+				"1UP".to_string(),
+				"SKY-1".to_string(),
+				"README.TXT".to_string(),
+			])
+		);
+
+		headers.bitmaps.pop();
+		headers.sprites.remove(0);
+		headers.tile16_names = None;
+		headers.misc_chunks = vec![MiscChunk::Article("STORY".to_string())];
+		assert_eq!(headers.check_c_identifiers(), Ok(()));
+	}
+
+	#[test]
+	fn validate_lump_coverage() {
+		let mut headers = GfxHeaders {
+			header_chunk_count: 3,
+			bitmaps: vec!["TITLE".to_string(), "CREDITS".to_string()],
+			..Default::default()
+		};
+		assert_eq!(headers.check_lump_coverage(), vec![3, 4]);
+		assert!(headers.validate(false, false).is_empty());
+		assert_eq!(
+			headers.validate(false, true),
+			vec![
+				ValidationWarning::UncoveredChunk {
+					chunk_id: 3,
+					name: "TITLEPIC".to_string()
+				},
+				ValidationWarning::UncoveredChunk {
+					chunk_id: 4,
+					name: "CREDITSPIC".to_string()
+				},
+			]
+		);
+
+		headers.lumps.push(Lump {
+			name: "TITLE".to_string(),
+			start_chunk: 3,
+			end_chunk: 3,
+			defined_at_line: 0,
+			section: ChunkSection::Bitmaps,
+		});
+		assert_eq!(headers.check_lump_coverage(), vec![4]);
+	}
+}
@@ -0,0 +1,300 @@
+/*
+ * idGrab: A header generator for ID-engine (Keen: Galaxy) games.
+ *
+ * Copyright (C) 2024 David Gow <david@davidgow.net>
+ *
+ * This software is provided 'as-is', without any express or implied warranty.
+ * In no event will the authors be held liable for any damages arising from
+ * the use of this software.
+ *
+ * Permission is granted to anyone to use this software for any purpose, including
+ * commercial applications, and to alter it and redistribute it freely, subject
+ * to the following restrictions.
+ *   1. The origin of this software must not be misrepresented; you must not
+ *      claim that you wrote the original software. If you use this software in
+ *      a product, an acknowledgment in the product documentation would be
+ *      appreciated but is not required.
+ *   2. Altered source versions must be plainly marked as such, and must not be
+ *      misrepresented as being the original software.
+ *   3. This notice may not be removed or altered from any source distribution.
+ */
+
+use std::process::{Command, Output};
+
+fn run_idgrab(args: &[&str]) -> Output {
+	Command::new(env!("CARGO_BIN_EXE_idgrab"))
+		.args(args)
+		.output()
+		.unwrap()
+}
+
+#[test]
+fn missing_flag_argument() {
+	for (flag, description) in [
+		("--gfxinfo", "a filename"),
+		("--igrab-version", "one of 0.24, 0.4"),
+		("--igrab-banner", "<text>"),
+	]
+	.iter()
+	{
+		let output = run_idgrab(&["scripts/keen4.idgrab", flag]);
+		assert_eq!(output.status.code(), Some(1));
+		let stderr = String::from_utf8(output.stderr).unwrap();
+		assert_eq!(
+			stderr,
+			format!("error: {} requires {}\n", flag, description)
+		);
+		assert!(String::from_utf8(output.stdout)
+			.unwrap()
+			.starts_with("Usage: idgrab"));
+	}
+}
+
+#[test]
+fn missing_extra_define_value() {
+	let output = run_idgrab(&["scripts/keen4.idgrab", "--igrab-extra-define", "GRAPHICSEG"]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"error: --igrab-extra-define requires <name> <value>\n"
+	);
+}
+
+#[test]
+fn all_formats_with_prefix() {
+	let out_dir = std::env::temp_dir().join(format!("idgrab-cli-all-{}", std::process::id()));
+	let _ = std::fs::remove_dir_all(&out_dir);
+	let prefix = out_dir.join("ck4");
+	/* The stock scripts' names aren't all C identifiers. */
+	let version_output = run_idgrab(&[
+		"scripts/keen4.idgrab",
+		"--igrab-allow-invalid-identifiers",
+		"--igrab-version",
+		"0.24",
+		"--igrab-header",
+		out_dir.join("expected.h").to_str().unwrap(),
+	]);
+	assert!(version_output.status.success());
+	let output = run_idgrab(&[
+		"scripts/keen4.idgrab",
+		"--igrab-allow-invalid-identifiers",
+		"--igrab-version",
+		"0.24",
+		"--all",
+		prefix.to_str().unwrap(),
+	]);
+	assert!(output.status.success());
+
+	let mut files: Vec<String> = std::fs::read_dir(&out_dir)
+		.unwrap()
+		.map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+		.collect();
+	files.sort();
+	assert_eq!(
+		files,
+		vec![
+			"GFXCK4.EQU",
+			"GFXCK4.H",
+			"ck4.def",
+			"ck4.gfx",
+			"ck4_gfx.txt",
+			"expected.h"
+		]
+	);
+	/*
+	 * --all writes the same header as --igrab-header with the same options,
+	 * apart from the generation time, with the timestamps feature.
+	 */
+	let read_header = |filename: &str| -> Vec<String> {
+		std::fs::read_to_string(out_dir.join(filename))
+			.unwrap()
+			.lines()
+			.filter(|line| !line.starts_with("// idGrab-ed on "))
+			.map(|line| line.to_string())
+			.collect()
+	};
+	assert_eq!(read_header("GFXCK4.H"), read_header("expected.h"));
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn invalid_c_identifiers() {
+	let out_dir = std::env::temp_dir().join(format!("idgrab-cli-ids-{}", std::process::id()));
+	let header = out_dir.join("GFXE_CK4.H");
+	let header = header.to_str().unwrap();
+	let error = "Error: \"100_PTS1\" isn't a valid C identifier, so will break C and assembly headers.\n";
+
+	/* The stock scripts have such names, so fail before writing anything. */
+	let output = run_idgrab(&["scripts/keen4.idgrab", "--igrab-header", header]);
+	assert_eq!(output.status.code(), Some(1));
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert!(stderr.starts_with(error));
+	assert!(stderr
+		.ends_with("Use --igrab-allow-invalid-identifiers to write the headers anyway.\n"));
+	assert!(!out_dir.exists());
+
+	let output = run_idgrab(&[
+		"scripts/keen4.idgrab",
+		"--igrab-allow-invalid-identifiers",
+		"--igrab-header",
+		header,
+	]);
+	assert!(output.status.success());
+	assert!(String::from_utf8(output.stderr)
+		.unwrap()
+		.starts_with(&error.replace("Error", "Warning")));
+
+	/* Other formats don't need identifiers. */
+	let output = run_idgrab(&[
+		"scripts/keen4.idgrab",
+		"--modid",
+		out_dir.join("MOD_CK4.DEF").to_str().unwrap(),
+	]);
+	assert!(output.status.success());
+	assert_eq!(String::from_utf8(output.stderr).unwrap(), "");
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn all_formats_without_demos() {
+	let out_dir =
+		std::env::temp_dir().join(format!("idgrab-cli-nodemo-{}", std::process::id()));
+	std::fs::create_dir_all(&out_dir).unwrap();
+	let script = out_dir.join("nodemo.idgrab");
+	std::fs::write(
+		&script,
+		"Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\nChunk \"README\"\n",
+	)
+	.unwrap();
+	let prefix = out_dir.join("pfx");
+	let output = run_idgrab(&[script.to_str().unwrap(), "--all", prefix.to_str().unwrap()]);
+	assert!(output.status.success());
+	for filename in [
+		"pfx.gfx",
+		"pfx.def",
+		"pfx_gfx.txt",
+		"GFXCK4.H",
+		"GFXCK4.EQU",
+	]
+	.iter()
+	{
+		assert!(
+			out_dir.join(filename).is_file(),
+			"{} wasn't written",
+			filename
+		);
+	}
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn all_formats_checks_gfxinfoe_header_chunks() {
+	let out_dir = std::env::temp_dir().join(format!("idgrab-cli-hdr-{}", std::process::id()));
+	std::fs::create_dir_all(&out_dir).unwrap();
+	let script = out_dir.join("header.idgrab");
+	std::fs::write(
+		&script,
+		"HeaderChunks 2\nBitmaps {\n\t\"TITLE\"\n}\nDemo 0\n",
+	)
+	.unwrap();
+	let prefix = out_dir.join("pfx");
+	let output = run_idgrab(&[script.to_str().unwrap(), "--all", prefix.to_str().unwrap()]);
+	assert_eq!(output.status.code(), Some(1));
+	assert!(String::from_utf8(output.stderr)
+		.unwrap()
+		.starts_with("Error: There are 2 header chunks, but GFXINFOE hardcodes"));
+	assert!(!out_dir.join("pfx.gfx").exists());
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn timestamps_flags() {
+	let out_dir =
+		std::env::temp_dir().join(format!("idgrab-cli-timestamps-{}", std::process::id()));
+	std::fs::create_dir_all(&out_dir).unwrap();
+	let script = out_dir.join("timestamps.idgrab");
+	std::fs::write(&script, "Bitmaps {\n\t\"TITLE\"\n}\n").unwrap();
+	let header = out_dir.join("GFXE_TMP.H");
+	/* Whichever features idGrab was built with, the last flag wins. */
+	for (flags, has_timestamp) in [
+		(&["--no-timestamps"][..], false),
+		(&["--timestamps"][..], true),
+		(&["--timestamps", "--no-timestamps"][..], false),
+	]
+	.iter()
+	{
+		let mut args = vec![script.to_str().unwrap()];
+		args.extend_from_slice(flags);
+		args.extend_from_slice(&["--igrab-header", header.to_str().unwrap()]);
+		assert!(run_idgrab(&args).status.success());
+		let contents = std::fs::read_to_string(&header).unwrap();
+		assert_eq!(
+			contents.contains("// idGrab-ed on "),
+			*has_timestamp,
+			"{:?}",
+			flags
+		);
+	}
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn constexpr_warning_printed_once() {
+	let out_dir =
+		std::env::temp_dir().join(format!("idgrab-cli-constexpr-{}", std::process::id()));
+	std::fs::create_dir_all(&out_dir).unwrap();
+	let script = out_dir.join("constexpr.idgrab");
+	std::fs::write(&script, "Extension \"CK4\"\nBitmaps {\n\t\"TITLE\"\n}\n").unwrap();
+	let prefix = out_dir.join("pfx");
+	let output = run_idgrab(&[
+		script.to_str().unwrap(),
+		"--igrab-constexpr",
+		"--all",
+		prefix.to_str().unwrap(),
+	]);
+	assert!(output.status.success());
+	let stderr = String::from_utf8(output.stderr).unwrap();
+	assert_eq!(stderr.matches("--igrab-constexpr has no effect").count(), 1);
+	std::fs::remove_dir_all(&out_dir).unwrap();
+}
+
+#[test]
+fn invalid_diff_format() {
+	let output = run_idgrab(&[
+		"--diff",
+		"scripts/keen4.idgrab",
+		"scripts/keen4.idgrab",
+		"--diff-format",
+		"xml",
+	]);
+	assert_eq!(output.status.code(), Some(1));
+	assert_eq!(
+		String::from_utf8(output.stderr).unwrap(),
+		"error: --diff-format requires one of text, json, csv\n"
+	);
+}
+
+#[test]
+fn invalid_flag_values() {
+	for (flag, value, description) in [
+		("--igrab-version", "0.3", "one of 0.24, 0.4"),
+		("--igrab-asm-style", "masm", "one of tasm, gas"),
+		(
+			"--igrab-misc-position",
+			"first",
+			"one of in-enum, after-amounts",
+		),
+		("--igrab-c-column", "abc", "<column>"),
+		("--igrab-enum-column", "-1", "<column>"),
+		("--omnispeak-game-ext", "maybe", "one of yes, no"),
+	]
+	.iter()
+	{
+		let output = run_idgrab(&["scripts/keen4.idgrab", flag, value]);
+		assert_eq!(output.status.code(), Some(1));
+		assert_eq!(
+			String::from_utf8(output.stderr).unwrap(),
+			format!("error: {} requires {}\n", flag, description)
+		);
+	}
+}